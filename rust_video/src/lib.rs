@@ -3,15 +3,19 @@ use anyhow::{Context, Result};
 use chrono::prelude::*;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
-use std::io::{BufReader, Read};
-use std::path::PathBuf;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
+use image::GrayImage;
 
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
 
 /// Python绑定的视频帧结构
 #[pyclass]
@@ -24,6 +28,9 @@ pub struct PyVideoFrame {
     #[pyo3(get)]
     pub height: usize,
     pub data: Vec<u8>,
+    /// 该帧的真实显示时间戳（秒），仅在 VFR 模式下通过 `-vf showinfo` 采集
+    #[pyo3(get, set)]
+    pub pts_time: Option<f64>,
 }
 
 #[pymethods]
@@ -42,6 +49,7 @@ impl PyVideoFrame {
             width,
             height,
             data: aligned_data,
+            pts_time: None,
         }
     }
     
@@ -68,10 +76,27 @@ impl PyVideoFrame {
     }
     
     /// 使用SIMD优化计算帧差异
-    #[pyo3(signature = (other, block_size=None))]
-    fn calculate_difference_simd(&self, other: &PyVideoFrame, block_size: Option<usize>) -> PyResult<f64> {
+    ///
+    /// `metric="sad"`（默认）走原本的逐像素平均绝对差；`metric="ssim"` 改为按8x8块计算结构相似度，
+    /// 返回 `1 - mean_ssim`，数值越大表示结构差异越大，语义上与SAD兼容，可直接复用同一套阈值判断；
+    /// `metric="psnr"` 返回真实的PSNR（dB，两帧完全相同时钳到100.0），数值越大代表两帧越接近，
+    /// 与前两者的"越大越不同"方向相反——在`extract_keyframes`里用`mode="threshold"`配合`metric="psnr"`时，
+    /// 判据是`PSNR < threshold`而不是`diff > threshold`。
+    #[pyo3(signature = (other, block_size=None, metric=None))]
+    fn calculate_difference_simd(&self, other: &PyVideoFrame, block_size: Option<usize>, metric: Option<&str>) -> PyResult<f64> {
         let block_size = block_size.unwrap_or(8192);
-        Ok(self.calculate_difference_parallel_simd(other, block_size, true))
+        match metric.unwrap_or("sad") {
+            "ssim" => Ok(self.calculate_ssim_diff(other)),
+            "psnr" => Ok(self.calculate_psnr(other, block_size)),
+            _ => Ok(self.calculate_difference_parallel_simd(other, block_size, true)),
+        }
+    }
+
+    /// 计算该帧的64位差分哈希（dHash）：缩小到9×8灰度，对每行相邻像素对逐一比较大小，
+    /// 每行8个比较位、8行共64位，拼成一个`u64`。汉明距离越小代表两帧画面越相似，
+    /// 可用来在关键帧候选里快速剔除视觉上冗余的镜头。
+    fn dhash(&self) -> u64 {
+        compute_dhash(&self.data, self.width, self.height)
     }
 }
 
@@ -103,6 +128,14 @@ impl PyVideoFrame {
                             }
                         }
                     }
+                    #[cfg(target_arch = "aarch64")]
+                    {
+                        unsafe {
+                            if std::arch::is_aarch64_feature_detected!("neon") {
+                                return self.calculate_difference_neon_block(&other.data, start, block_len);
+                            }
+                        }
+                    }
                 }
                 
                 // 标量实现回退
@@ -170,9 +203,144 @@ impl PyVideoFrame {
         for i in (start + chunks * 16)..(start + len) {
             total_diff += (self.data[i] as i32 - other_data[i] as i32).abs() as u64;
         }
-        
+
+        total_diff
+    }
+
+    /// NEON 优化的块处理（ARM/aarch64，例如 Apple Silicon）
+    #[cfg(target_arch = "aarch64")]
+    #[target_feature(enable = "neon")]
+    unsafe fn calculate_difference_neon_block(&self, other_data: &[u8], start: usize, len: usize) -> u64 {
+        let mut total_diff = 0u64;
+        let chunks = len / 16;
+
+        for i in 0..chunks {
+            let offset = start + i * 16;
+
+            let a = vld1q_u8(self.data.as_ptr().add(offset));
+            let b = vld1q_u8(other_data.as_ptr().add(offset));
+
+            // 逐字节绝对差，再宽化累加到u64，避免16字节求和时溢出u8/u16
+            let abs_diff = vabdq_u8(a, b);
+            let sum16 = vpaddlq_u8(abs_diff);
+            let sum32 = vpaddlq_u16(sum16);
+            let sum64 = vpaddlq_u32(sum32);
+
+            total_diff += vgetq_lane_u64(sum64, 0) + vgetq_lane_u64(sum64, 1);
+        }
+
+        // 处理剩余字节
+        for i in (start + chunks * 16)..(start + len) {
+            total_diff += (self.data[i] as i32 - other_data[i] as i32).abs() as u64;
+        }
+
         total_diff
     }
+
+    /// 基于8x8非重叠块的结构相似度（SSIM），返回 `1 - mean_ssim` 以便和SAD共用同一套阈值逻辑
+    fn calculate_ssim_diff(&self, other: &PyVideoFrame) -> f64 {
+        if self.width != other.width || self.height != other.height {
+            return f64::MAX;
+        }
+
+        const BLOCK: usize = 8;
+        const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+        const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+        let width = self.width;
+        let height = self.height;
+        let blocks_y = (height + BLOCK - 1) / BLOCK;
+        let blocks_x = (width + BLOCK - 1) / BLOCK;
+
+        let ssim_sum: f64 = (0..blocks_y)
+            .into_par_iter()
+            .map(|by| {
+                let mut row_sum = 0.0f64;
+                let y0 = by * BLOCK;
+                let y1 = (y0 + BLOCK).min(height);
+                for bx in 0..blocks_x {
+                    let x0 = bx * BLOCK;
+                    let x1 = (x0 + BLOCK).min(width);
+
+                    let mut sum_x = 0.0f64;
+                    let mut sum_y = 0.0f64;
+                    let mut n = 0.0f64;
+                    for y in y0..y1 {
+                        for x in x0..x1 {
+                            let idx = y * width + x;
+                            sum_x += self.data[idx] as f64;
+                            sum_y += other.data[idx] as f64;
+                            n += 1.0;
+                        }
+                    }
+                    let mean_x = sum_x / n;
+                    let mean_y = sum_y / n;
+
+                    let mut var_x = 0.0f64;
+                    let mut var_y = 0.0f64;
+                    let mut covar = 0.0f64;
+                    for y in y0..y1 {
+                        for x in x0..x1 {
+                            let idx = y * width + x;
+                            let dx = self.data[idx] as f64 - mean_x;
+                            let dy = other.data[idx] as f64 - mean_y;
+                            var_x += dx * dx;
+                            var_y += dy * dy;
+                            covar += dx * dy;
+                        }
+                    }
+                    var_x /= n;
+                    var_y /= n;
+                    covar /= n;
+
+                    let ssim = ((2.0 * mean_x * mean_y + C1) * (2.0 * covar + C2))
+                        / ((mean_x * mean_x + mean_y * mean_y + C1) * (var_x + var_y + C2));
+                    row_sum += ssim;
+                }
+                row_sum
+            })
+            .sum();
+
+        let total_blocks = (blocks_y * blocks_x) as f64;
+        let mean_ssim = ssim_sum / total_blocks;
+        1.0 - mean_ssim
+    }
+
+    /// 峰值信噪比（PSNR），按`calculate_difference_parallel_simd`同样的分块+并行结构累加平方差——
+    /// 只是AVX2/NEON路径算的是绝对差之和，这里换成标量平方差之和，分块与并行方式保持一致。
+    /// MSE = 平方差均值，PSNR = 10·log10(255²/MSE)；两帧完全相同（MSE≈0）时钳到100.0表示"无穷大"，
+    /// 避免对0取log。数值越大代表两帧越接近，与SAD/SSIM"越大越不同"的方向相反。
+    fn calculate_psnr(&self, other: &PyVideoFrame, block_size: usize) -> f64 {
+        if self.width != other.width || self.height != other.height {
+            return 0.0;
+        }
+
+        let total_pixels = self.width * self.height;
+        let num_blocks = (total_pixels + block_size - 1) / block_size;
+
+        let sum_sq: f64 = (0..num_blocks)
+            .into_par_iter()
+            .map(|block_idx| {
+                let start = block_idx * block_size;
+                let end = ((block_idx + 1) * block_size).min(total_pixels);
+                self.data[start..end]
+                    .iter()
+                    .zip(other.data[start..end].iter())
+                    .map(|(a, b)| {
+                        let d = *a as f64 - *b as f64;
+                        d * d
+                    })
+                    .sum::<f64>()
+            })
+            .sum();
+
+        let mse = sum_sq / total_pixels as f64;
+        if mse <= f64::EPSILON {
+            100.0
+        } else {
+            (10.0 * (255.0 * 255.0 / mse).log10()).min(100.0)
+        }
+    }
 }
 
 /// 性能测试结果
@@ -207,6 +375,19 @@ pub struct PyPerformanceResult {
     pub threads_used: usize,
     #[pyo3(get)]
     pub timestamp: String,
+    /// dHash去重后剩余的关键帧数量；不做去重（`dedup_distance=0`，或不去重的旁路/流式/批处理路径）
+    /// 且未用`codec_keyframes`合并时与`keyframes_extracted`相同
+    #[pyo3(get)]
+    pub keyframes_after_dedup: usize,
+    /// 本次分析实际使用的起始时间（秒），未指定`start`时为0.0
+    #[pyo3(get)]
+    pub analysis_start_s: f64,
+    /// 本次分析实际覆盖的时长（秒），`None`代表一直分析到文件末尾
+    #[pyo3(get)]
+    pub analysis_duration_s: Option<f64>,
+    /// 抽帧前用`fps`滤镜降采样到的目标帧率，`None`代表保留视频原始帧率
+    #[pyo3(get)]
+    pub analysis_fps: Option<f64>,
 }
 
 #[pymethods]
@@ -229,24 +410,73 @@ impl PyPerformanceResult {
             dict.insert("simd_enabled".to_string(), self.simd_enabled.to_object(py));
             dict.insert("threads_used".to_string(), self.threads_used.to_object(py));
             dict.insert("timestamp".to_string(), self.timestamp.to_object(py));
+            dict.insert("keyframes_after_dedup".to_string(), self.keyframes_after_dedup.to_object(py));
+            dict.insert("analysis_start_s".to_string(), self.analysis_start_s.to_object(py));
+            dict.insert("analysis_duration_s".to_string(), self.analysis_duration_s.to_object(py));
+            dict.insert("analysis_fps".to_string(), self.analysis_fps.to_object(py));
             Ok(dict)
         })
     }
 }
 
+/// `process_directory` 批处理中单个文件的失败记录
+#[pyclass]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PyBatchFileError {
+    #[pyo3(get)]
+    pub video_file: String,
+    #[pyo3(get)]
+    pub error: String,
+}
+
+/// `process_directory` 的汇总结果：逐文件结果、失败列表，以及整批的统计数据
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct PyBatchResult {
+    #[pyo3(get)]
+    pub results: Vec<PyPerformanceResult>,
+    #[pyo3(get)]
+    pub errors: Vec<PyBatchFileError>,
+    #[pyo3(get)]
+    pub total_files: usize,
+    #[pyo3(get)]
+    pub succeeded: usize,
+    #[pyo3(get)]
+    pub failed: usize,
+    #[pyo3(get)]
+    pub total_frames: usize,
+    #[pyo3(get)]
+    pub mean_processing_fps: f64,
+    #[pyo3(get)]
+    pub total_time_ms: f64,
+}
+
 /// 主要的视频关键帧提取器类
 #[pyclass]
 pub struct VideoKeyframeExtractor {
     ffmpeg_path: String,
     threads: usize,
     verbose: bool,
+    streaming: bool,
+    max_memory_frames: usize,
+    hwaccel: Option<String>,
+    decoder_backend: String,
 }
 
 #[pymethods]
 impl VideoKeyframeExtractor {
     #[new]
-    #[pyo3(signature = (ffmpeg_path = "ffmpeg".to_string(), threads = 0, verbose = false))]
-    fn new(ffmpeg_path: String, threads: usize, verbose: bool) -> PyResult<Self> {
+    #[pyo3(signature = (ffmpeg_path = "ffmpeg".to_string(), threads = 0, verbose = false, streaming = false, max_memory_frames = 0, hwaccel = None, decoder_backend = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        ffmpeg_path: String,
+        threads: usize,
+        verbose: bool,
+        streaming: bool,
+        max_memory_frames: usize,
+        hwaccel: Option<String>,
+        decoder_backend: Option<String>,
+    ) -> PyResult<Self> {
         // 设置线程池（如果还没有初始化）
         if threads > 0 {
             // 尝试设置线程池，如果已经初始化则忽略错误
@@ -254,60 +484,97 @@ impl VideoKeyframeExtractor {
                 .num_threads(threads)
                 .build_global();
         }
-        
+
         Ok(Self {
             ffmpeg_path,
             threads: if threads == 0 { rayon::current_num_threads() } else { threads },
             verbose,
+            streaming,
+            max_memory_frames,
+            hwaccel,
+            decoder_backend: decoder_backend.unwrap_or_else(|| "subprocess".to_string()),
         })
     }
-    
+
     /// 从视频中提取帧
-    #[pyo3(signature = (video_path, max_frames=None))]
-    fn extract_frames(&self, video_path: &str, max_frames: Option<usize>) -> PyResult<(Vec<PyVideoFrame>, usize, usize)> {
+    ///
+    /// `vfr=True` 时会额外让 FFmpeg 附加 `-vf showinfo` 滤镜，逐帧采集真实的 `pts_time`
+    /// 并写入每个 `PyVideoFrame.pts_time`，供可变帧率素材做精确的关键帧回溯。
+    ///
+    /// `decoder_backend="ffmpeg_next"`（构造时指定）时不再fork子进程，而是用`ffmpeg-next`绑定
+    /// 直接在进程内解码+缩放到灰度，省掉逐帧管道拷贝；该后端不支持`vfr`的showinfo时间戳采集，
+    /// 传入`vfr=True`时会被忽略，返回帧的`pts_time`保持为`None`。
+    #[pyo3(signature = (video_path, max_frames=None, vfr=None))]
+    fn extract_frames(&self, video_path: &str, max_frames: Option<usize>, vfr: Option<bool>) -> PyResult<(Vec<PyVideoFrame>, usize, usize, f64)> {
         let video_path = PathBuf::from(video_path);
         let max_frames = max_frames.unwrap_or(0);
-        
-        extract_frames_memory_stream(&video_path, &PathBuf::from(&self.ffmpeg_path), max_frames, self.verbose)
+        let vfr = vfr.unwrap_or(false);
+
+        extract_frames_auto(&video_path, &PathBuf::from(&self.ffmpeg_path), max_frames, self.verbose, vfr, None, None, None, self.hwaccel.as_deref(), &self.decoder_backend)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Frame extraction failed: {}", e)))
     }
     
     /// 提取关键帧索引
-    #[pyo3(signature = (frames, threshold, use_simd=None, block_size=None))]
+    #[pyo3(signature = (frames, threshold, use_simd=None, block_size=None, mode=None, min_scene_len=None, max_scene_len=None, k=None, window_size=None, metric=None))]
+    #[allow(clippy::too_many_arguments)]
     fn extract_keyframes(
-        &self, 
-        frames: Vec<PyVideoFrame>, 
-        threshold: f64, 
+        &self,
+        frames: Vec<PyVideoFrame>,
+        threshold: f64,
         use_simd: Option<bool>,
-        block_size: Option<usize>
+        block_size: Option<usize>,
+        mode: Option<&str>,
+        min_scene_len: Option<usize>,
+        max_scene_len: Option<usize>,
+        k: Option<f64>,
+        window_size: Option<usize>,
+        metric: Option<&str>,
     ) -> PyResult<Vec<usize>> {
         let use_simd = use_simd.unwrap_or(true);
         let block_size = block_size.unwrap_or(8192);
-        
-        extract_keyframes_optimized(&frames, threshold, use_simd, block_size, self.verbose)
+        let mode = mode.unwrap_or("threshold");
+        let min_scene_len = min_scene_len.unwrap_or(0);
+        let max_scene_len = max_scene_len.unwrap_or(usize::MAX);
+        let k = k.unwrap_or(2.5);
+        let window_size = window_size.unwrap_or(30);
+        let metric = metric.unwrap_or("sad");
+
+        extract_keyframes_optimized(
+            &frames, threshold, use_simd, block_size, self.verbose,
+            mode, min_scene_len, max_scene_len, k, window_size, metric,
+        )
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Keyframe extraction failed: {}", e)))
     }
     
     /// 保存关键帧为图片
-    #[pyo3(signature = (video_path, keyframe_indices, output_dir, max_save=None))]
+    ///
+    /// `fps` 为真实的平均帧率（而非硬编码的 30），用于将 `frame_idx` 换算为 seek 时间戳；
+    /// 若传入 `frame_pts`（与 `extract_frames(vfr=True)` 采集到的 `pts_time` 对应），
+    /// 则优先使用对应帧的真实时间戳，避免 VFR 素材上的漂移。
+    #[pyo3(signature = (video_path, keyframe_indices, output_dir, max_save=None, fps=None, frame_pts=None))]
     fn save_keyframes(
         &self,
         video_path: &str,
         keyframe_indices: Vec<usize>,
         output_dir: &str,
-        max_save: Option<usize>
+        max_save: Option<usize>,
+        fps: Option<f64>,
+        frame_pts: Option<Vec<f64>>,
     ) -> PyResult<usize> {
         let video_path = PathBuf::from(video_path);
         let output_dir = PathBuf::from(output_dir);
         let max_save = max_save.unwrap_or(50);
-        
+        let fps = fps.unwrap_or(30.0);
+
         save_keyframes_optimized(
             &video_path,
             &keyframe_indices,
             &output_dir,
             &PathBuf::from(&self.ffmpeg_path),
             max_save,
-            self.verbose
+            self.verbose,
+            fps,
+            frame_pts.as_deref(),
         ).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Save keyframes failed: {}", e)))
     }
     
@@ -335,6 +602,11 @@ impl VideoKeyframeExtractor {
             max_frames,
             use_simd,
             block_size,
+            None,
+            None,
+            None,
+            self.hwaccel.as_deref(),
+            &self.decoder_backend,
             self.verbose
         ).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Benchmark failed: {}", e)))?;
         
@@ -353,11 +625,38 @@ impl VideoKeyframeExtractor {
             simd_enabled: result.simd_enabled,
             threads_used: result.threads_used,
             timestamp: result.timestamp,
+            keyframes_after_dedup: result.keyframes_after_dedup,
+            analysis_start_s: 0.0,
+            analysis_duration_s: None,
+            analysis_fps: None,
         })
     }
-    
+
     /// 完整的处理流程
-    #[pyo3(signature = (video_path, output_dir, threshold=None, max_frames=None, max_save=None, use_simd=None, block_size=None))]
+    ///
+    /// `codec_keyframes="intersect"/"union"` 时会额外用ffprobe查询编码器的真实I帧，
+    /// 与差异检测得到的关键帧取交集/并集后再保存，确保落盘的静态图都能对齐到可解码的I帧。
+    ///
+    /// `container_keyframes_only=True` 时完全跳过解码+SIMD差分两步：直接走容器的sync-sample
+    /// 信息（MP4/MOV先直接解析`stbl`/`stss`，解析不到时回退到`get_container_keyframe_timestamps_ffprobe`）
+    /// 拿到编码器关键帧时间戳并据此截图，
+    /// 适合只想要"编码关键帧"而不关心内容自适应场景切换的场合。
+    ///
+    /// `dedup_distance`（默认约5）开启dHash近重复剔除：检测/合并后的候选帧按顺序贪心比对，
+    /// 谁跟某个已接受的帧汉明距离小于该值就被丢弃，用于清掉差分/场景检测仍会放行的冗余镜头。
+    ///
+    /// `start`/`duration`（秒）把分析范围限制到视频的一段：内部转成FFmpeg `-ss <start> -t <duration>`
+    /// 并放在`-i`之前触发快速seek，不用解码到目标位置前的所有帧。`fps`在抽帧前叠加`fps=<rate>`滤镜，
+    /// 把输入降采样到目标帧率再分析，用于长录像只要稀疏采样的场景。三者只对默认的subprocess逐帧解码路径生效，
+    /// `container_keyframes_only=True`/`streaming=True`/`decoder_backend="ffmpeg_next"`（构造时指定）
+    /// 以及Y4M输入下会被忽略。实际生效的范围/帧率
+    /// 记录在返回结果的`analysis_start_s`/`analysis_duration_s`/`analysis_fps`上。
+    ///
+    /// `emit_segments=True` 时保存阶段不再截JPEG静态图，而是调用`save_keyframes_as_segments`
+    /// 把关键帧切点当成fragment边界，产出一个fragmented MP4（`segments.mp4`）和一份记录每个
+    /// fragment起始帧/时间戳的`segments_manifest.json`，用于自适应流媒体分发管线。
+    #[pyo3(signature = (video_path, output_dir, threshold=None, max_frames=None, max_save=None, use_simd=None, block_size=None, codec_keyframes=None, container_keyframes_only=None, dedup_distance=None, start=None, duration=None, fps=None, emit_segments=None))]
+    #[allow(clippy::too_many_arguments)]
     fn process_video(
         &self,
         video_path: &str,
@@ -366,17 +665,105 @@ impl VideoKeyframeExtractor {
         max_frames: Option<usize>,
         max_save: Option<usize>,
         use_simd: Option<bool>,
-        block_size: Option<usize>
+        block_size: Option<usize>,
+        codec_keyframes: Option<&str>,
+        container_keyframes_only: Option<bool>,
+        dedup_distance: Option<u32>,
+        start: Option<f64>,
+        duration: Option<f64>,
+        fps: Option<f64>,
+        emit_segments: Option<bool>,
     ) -> PyResult<PyPerformanceResult> {
         let threshold = threshold.unwrap_or(2.0);
         let max_frames = max_frames.unwrap_or(0);
         let max_save = max_save.unwrap_or(50);
         let use_simd = use_simd.unwrap_or(true);
         let block_size = block_size.unwrap_or(8192);
-        
+        // ffmpeg_next后端和Y4M输入不支持时间窗/降采样（见`extract_frames_auto`），此时按整段原始帧率处理和记录
+        let windowing_applied = self.decoder_backend != "ffmpeg_next" && !is_y4m_input(Path::new(video_path));
+        let (start, duration, fps) = if windowing_applied { (start, duration, fps) } else { (None, None, None) };
+        let analysis_start_s = start.unwrap_or(0.0);
+
         let video_path_buf = PathBuf::from(video_path);
         let output_dir_buf = PathBuf::from(output_dir);
-        
+
+        // 旁路模式：不解码、不做SIMD差分，直接读容器sync-sample表拿到的真实关键帧时间戳去截图
+        if container_keyframes_only.unwrap_or(false) {
+            let result = process_video_container_keyframes(
+                &video_path_buf,
+                &output_dir_buf,
+                &PathBuf::from(&self.ffmpeg_path),
+                max_save,
+                self.verbose,
+            ).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Container keyframe processing failed: {}", e)))?;
+
+            return Ok(PyPerformanceResult {
+                test_name: result.test_name,
+                video_file: result.video_file,
+                total_time_ms: result.total_time_ms,
+                frame_extraction_time_ms: result.frame_extraction_time_ms,
+                keyframe_analysis_time_ms: result.keyframe_analysis_time_ms,
+                total_frames: result.total_frames,
+                keyframes_extracted: result.keyframes_extracted,
+                keyframe_ratio: result.keyframe_ratio,
+                processing_fps: result.processing_fps,
+                threshold: result.threshold,
+                optimization_type: result.optimization_type,
+                simd_enabled: result.simd_enabled,
+                threads_used: result.threads_used,
+                timestamp: result.timestamp,
+                keyframes_after_dedup: result.keyframes_after_dedup,
+                // 旁路模式直接读容器sync-sample表，不支持时间窗裁剪/降采样，始终按整段原始帧率处理
+                analysis_start_s: 0.0,
+                analysis_duration_s: None,
+                analysis_fps: None,
+            });
+        }
+
+        // 低内存路径：不在内存中保留整段视频的帧，边读边分析边丢弃
+        if self.streaming {
+            let result = process_video_streaming(
+                &video_path_buf,
+                &output_dir_buf,
+                &PathBuf::from(&self.ffmpeg_path),
+                threshold,
+                max_frames,
+                max_save,
+                use_simd,
+                block_size,
+                "threshold",
+                0,
+                usize::MAX,
+                2.5,
+                30,
+                self.max_memory_frames,
+                self.hwaccel.as_deref(),
+                self.verbose,
+            ).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Streaming processing failed: {}", e)))?;
+
+            return Ok(PyPerformanceResult {
+                test_name: result.test_name,
+                video_file: result.video_file,
+                total_time_ms: result.total_time_ms,
+                frame_extraction_time_ms: result.frame_extraction_time_ms,
+                keyframe_analysis_time_ms: result.keyframe_analysis_time_ms,
+                total_frames: result.total_frames,
+                keyframes_extracted: result.keyframes_extracted,
+                keyframe_ratio: result.keyframe_ratio,
+                processing_fps: result.processing_fps,
+                threshold: result.threshold,
+                optimization_type: result.optimization_type,
+                simd_enabled: result.simd_enabled,
+                threads_used: result.threads_used,
+                timestamp: result.timestamp,
+                keyframes_after_dedup: result.keyframes_after_dedup,
+                // 流式路径同样不支持时间窗裁剪/降采样，始终按整段原始帧率处理
+                analysis_start_s: 0.0,
+                analysis_duration_s: None,
+                analysis_fps: None,
+            });
+        }
+
         // 运行性能测试
         let result = run_performance_test(
             &video_path_buf,
@@ -386,26 +773,71 @@ impl VideoKeyframeExtractor {
             max_frames,
             use_simd,
             block_size,
+            start,
+            duration,
+            fps,
+            self.hwaccel.as_deref(),
+            &self.decoder_backend,
             self.verbose
         ).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Processing failed: {}", e)))?;
-        
+
         // 提取并保存关键帧
-        let (frames, _, _) = extract_frames_memory_stream(&video_path_buf, &PathBuf::from(&self.ffmpeg_path), max_frames, self.verbose)
+        let (frames, _, _, native_fps) = extract_frames_auto(&video_path_buf, &PathBuf::from(&self.ffmpeg_path), max_frames, self.verbose, false, start, duration, fps, self.hwaccel.as_deref(), &self.decoder_backend)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Frame extraction failed: {}", e)))?;
-        
+
         let frames: Vec<PyVideoFrame> = frames.into_iter().map(|f| PyVideoFrame {
             frame_number: f.frame_number,
             width: f.width,
             height: f.height,
             data: f.data,
+            pts_time: f.pts_time,
         }).collect();
-        
-        let keyframe_indices = extract_keyframes_optimized(&frames, threshold, use_simd, block_size, self.verbose)
+
+        let mut keyframe_indices = extract_keyframes_optimized(
+            &frames, threshold, use_simd, block_size, self.verbose,
+            "threshold", 0, usize::MAX, 2.5, 30, "sad",
+        )
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Keyframe extraction failed: {}", e)))?;
-        
-        save_keyframes_optimized(&video_path_buf, &keyframe_indices, &output_dir_buf, &PathBuf::from(&self.ffmpeg_path), max_save, self.verbose)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Save keyframes failed: {}", e)))?;
-        
+
+        if let Some(combine_mode) = codec_keyframes {
+            let ffprobe_path = derive_ffprobe_path(Path::new(&self.ffmpeg_path));
+            let codec_indices = get_codec_keyframes_ffprobe(&video_path_buf, ffprobe_path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Codec keyframe probing failed: {}", e)))?;
+
+            keyframe_indices = match combine_mode {
+                "intersect" => keyframe_indices.into_iter().filter(|i| codec_indices.contains(i)).collect(),
+                "union" => {
+                    let mut merged: Vec<usize> = keyframe_indices.into_iter().chain(codec_indices).collect();
+                    merged.sort_unstable();
+                    merged.dedup();
+                    merged
+                }
+                _ => keyframe_indices,
+            };
+        }
+
+        // dHash近重复剔除：贪心地丢弃与已接受帧汉明距离过小的候选，减少输出里视觉雷同的截图；
+        // 未传`dedup_distance`时按5处理，传0则不剔除任何帧（汉明距离不会小于0）
+        let keyframe_indices = dedup_keyframes_by_hash(&frames, &keyframe_indices, dedup_distance.unwrap_or(5));
+
+        // `start`/`fps`裁剪过的流里，帧下标相对的是裁剪后的时间轴；重建成原视频上的绝对时间戳，
+        // 复用`frame_pts`这个本来给VFR真实时间戳用的参数，而不是让`save_keyframes_optimized`
+        // 按原始`native_fps`去seek，否则裁剪开头会被跳过、降采样后的下标也会对不上实际位置。
+        let windowed_pts = if start.is_some() || fps.is_some() {
+            let effective_fps = fps.unwrap_or(native_fps);
+            Some((0..frames.len()).map(|i| analysis_start_s + i as f64 / effective_fps).collect::<Vec<f64>>())
+        } else {
+            None
+        };
+
+        if emit_segments.unwrap_or(false) {
+            save_keyframes_as_segments(&video_path_buf, &keyframe_indices, &output_dir_buf, &PathBuf::from(&self.ffmpeg_path), frames.len(), self.verbose, native_fps, windowed_pts.as_deref())
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Segment emission failed: {}", e)))?;
+        } else {
+            save_keyframes_optimized(&video_path_buf, &keyframe_indices, &output_dir_buf, &PathBuf::from(&self.ffmpeg_path), max_save, self.verbose, native_fps, windowed_pts.as_deref())
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Save keyframes failed: {}", e)))?;
+        }
+
         Ok(PyPerformanceResult {
             test_name: result.test_name,
             video_file: result.video_file,
@@ -421,9 +853,56 @@ impl VideoKeyframeExtractor {
             simd_enabled: result.simd_enabled,
             threads_used: result.threads_used,
             timestamp: result.timestamp,
+            keyframes_after_dedup: keyframe_indices.len(),
+            analysis_start_s,
+            analysis_duration_s: duration,
+            analysis_fps: fps,
         })
     }
-    
+
+    /// 多指标报告模式：抽帧后对每一对相邻帧同时算SAD/PSNR/SSIM（共享一次`par_windows(2)`
+    /// 并行遍历，而不是跑三趟`extract_keyframes`），写出一份按帧号/时间戳/三个指标/
+    /// `is_keyframe`（仅按SAD对`threshold`做固定阈值判断，供参考）排列的时间序列。
+    /// `output_path`以`.json`结尾写JSON，否则写CSV。用于在真正选定metric/threshold抽帧前，
+    /// 先看看SAD/PSNR/SSIM在这段素材上各自的分布，复用了`calculate_difference_parallel_simd`/
+    /// `calculate_psnr`/`calculate_ssim_diff`已有的实现，不重新写一套指标计算。
+    #[pyo3(signature = (video_path, output_path, threshold=None, max_frames=None, use_simd=None, block_size=None))]
+    fn generate_report(
+        &self,
+        video_path: &str,
+        output_path: &str,
+        threshold: Option<f64>,
+        max_frames: Option<usize>,
+        use_simd: Option<bool>,
+        block_size: Option<usize>,
+    ) -> PyResult<usize> {
+        let threshold = threshold.unwrap_or(2.0);
+        let max_frames = max_frames.unwrap_or(0);
+        let use_simd = use_simd.unwrap_or(true);
+        let block_size = block_size.unwrap_or(8192);
+
+        let video_path_buf = PathBuf::from(video_path);
+
+        let (frames, _, _, native_fps) = extract_frames_auto(&video_path_buf, &PathBuf::from(&self.ffmpeg_path), max_frames, self.verbose, false, None, None, None, self.hwaccel.as_deref(), &self.decoder_backend)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Frame extraction failed: {}", e)))?;
+
+        let frames: Vec<PyVideoFrame> = frames.into_iter().map(|f| PyVideoFrame {
+            frame_number: f.frame_number,
+            width: f.width,
+            height: f.height,
+            data: f.data,
+            pts_time: f.pts_time,
+        }).collect();
+
+        let records = generate_keyframe_report(&frames, threshold, use_simd, block_size, native_fps, None);
+        let row_count = records.len();
+
+        write_keyframe_report(&records, output_path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to write keyframe report: {}", e)))?;
+
+        Ok(row_count)
+    }
+
     /// 获取CPU特性信息
     fn get_cpu_features(&self) -> PyResult<HashMap<String, bool>> {
         let mut features = HashMap::new();
@@ -437,11 +916,16 @@ impl VideoKeyframeExtractor {
             features.insert("fma".to_string(), std::arch::is_x86_feature_detected!("fma"));
         }
         
-        #[cfg(not(target_arch = "x86_64"))]
+        #[cfg(target_arch = "aarch64")]
+        {
+            features.insert("neon".to_string(), std::arch::is_aarch64_feature_detected!("neon"));
+        }
+
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
         {
             features.insert("simd_supported".to_string(), false);
         }
-        
+
         Ok(features)
     }
     
@@ -459,6 +943,161 @@ impl VideoKeyframeExtractor {
     fn get_actual_thread_count(&self) -> usize {
         rayon::current_num_threads()
     }
+
+    /// 查询编码器真实的关键帧（I帧）下标，用ffprobe读取码流的`pict_type`标记，
+    /// 不做任何解码/差分计算，可用于验证差异检测结果或为快速seek提供准确落点。
+    fn get_codec_keyframes(&self, video_path: &str) -> PyResult<Vec<usize>> {
+        let ffprobe_path = derive_ffprobe_path(Path::new(&self.ffmpeg_path));
+        get_codec_keyframes_ffprobe(video_path, ffprobe_path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Codec keyframe probing failed: {}", e)))
+    }
+
+    /// 读取容器sync-sample信息得到的关键帧时间戳（秒），用`ffprobe -skip_frame nokey`只枚举
+    /// 被标记为关键帧的包，全程不解码任何非关键帧，比`get_codec_keyframes`逐帧扫描更快。
+    fn get_container_keyframe_timestamps(&self, video_path: &str) -> PyResult<Vec<f64>> {
+        let ffprobe_path = derive_ffprobe_path(Path::new(&self.ffmpeg_path));
+        get_container_keyframe_timestamps_ffprobe(video_path, ffprobe_path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Container keyframe probing failed: {}", e)))
+    }
+
+    /// 把已选中的关键帧拼成一张contact sheet（N张一行，带gutter），并用median-cut量化成
+    /// 不超过`palette_size`级灰度以压小体积，直接复用`extract_frames`已解码的帧，无需再次seek。
+    #[pyo3(signature = (frames, keyframe_indices, output_path, columns=None, palette_size=None, thumb_width=None, gutter=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn save_montage(
+        &self,
+        frames: Vec<PyVideoFrame>,
+        keyframe_indices: Vec<usize>,
+        output_path: &str,
+        columns: Option<usize>,
+        palette_size: Option<usize>,
+        thumb_width: Option<usize>,
+        gutter: Option<usize>,
+    ) -> PyResult<()> {
+        let columns = columns.unwrap_or(8);
+        let palette_size = palette_size.unwrap_or(256);
+        let thumb_width = thumb_width.unwrap_or(160);
+        let gutter = gutter.unwrap_or(4);
+
+        save_montage_optimized(
+            &frames,
+            &keyframe_indices,
+            Path::new(output_path),
+            columns,
+            palette_size,
+            thumb_width,
+            gutter,
+            self.verbose,
+        ).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Montage generation failed: {}", e)))
+    }
+
+    /// 批量处理一个目录：递归扫描`extensions`匹配的视频文件（默认常见容器后缀），用rayon线程池
+    /// 并行跑完整处理流程，每个文件各存到`output_dir`下以文件名命名的子目录；单个文件失败只记
+    /// 录到`errors`里，不会中断其余文件。
+    ///
+    /// `progress_callback` 每完成一个文件就从工作线程回调一次
+    /// `callback(completed: int, total: int, current_fps: float)`，可直接桥接到Python侧的
+    /// `tqdm` 等进度条。
+    #[pyo3(signature = (input_dir, output_dir, extensions=None, threshold=None, max_frames=None, max_save=None, use_simd=None, block_size=None, progress_callback=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn process_directory(
+        &self,
+        py: Python<'_>,
+        input_dir: &str,
+        output_dir: &str,
+        extensions: Option<Vec<String>>,
+        threshold: Option<f64>,
+        max_frames: Option<usize>,
+        max_save: Option<usize>,
+        use_simd: Option<bool>,
+        block_size: Option<usize>,
+        progress_callback: Option<PyObject>,
+    ) -> PyResult<PyBatchResult> {
+        let input_dir = PathBuf::from(input_dir);
+        let output_dir = PathBuf::from(output_dir);
+        let extensions = extensions.unwrap_or_else(|| {
+            ["mp4", "mkv", "avi", "mov", "webm", "flv", "wmv", "m4v"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        });
+        let threshold = threshold.unwrap_or(2.0);
+        let max_frames = max_frames.unwrap_or(0);
+        let max_save = max_save.unwrap_or(50);
+        let use_simd = use_simd.unwrap_or(true);
+        let block_size = block_size.unwrap_or(8192);
+
+        let batch_start = Instant::now();
+        // 释放GIL后再进入rayon并行区域：工作线程会在每完成一个文件后通过
+        // `Python::with_gil`临时获取GIL来调用`progress_callback`，若此处不释放，
+        // 持有GIL的主线程会一直阻塞在`.collect()`上，造成死锁。
+        let (results, errors) = py.allow_threads(|| {
+            process_directory_batch(
+                &input_dir,
+                &output_dir,
+                &PathBuf::from(&self.ffmpeg_path),
+                &extensions,
+                threshold,
+                max_frames,
+                max_save,
+                use_simd,
+                block_size,
+                self.hwaccel.as_deref(),
+                self.verbose,
+                progress_callback,
+            )
+        });
+        let total_time_ms = batch_start.elapsed().as_secs_f64() * 1000.0;
+
+        let succeeded = results.len();
+        let failed = errors.len();
+        let total_frames: usize = results.iter().map(|r| r.total_frames).sum();
+        let mean_processing_fps = if results.is_empty() {
+            0.0
+        } else {
+            results.iter().map(|r| r.processing_fps).sum::<f64>() / succeeded as f64
+        };
+
+        let results: Vec<PyPerformanceResult> = results
+            .into_iter()
+            .map(|r| PyPerformanceResult {
+                test_name: r.test_name,
+                video_file: r.video_file,
+                total_time_ms: r.total_time_ms,
+                frame_extraction_time_ms: r.frame_extraction_time_ms,
+                keyframe_analysis_time_ms: r.keyframe_analysis_time_ms,
+                total_frames: r.total_frames,
+                keyframes_extracted: r.keyframes_extracted,
+                keyframe_ratio: r.keyframe_ratio,
+                processing_fps: r.processing_fps,
+                threshold: r.threshold,
+                optimization_type: r.optimization_type,
+                simd_enabled: r.simd_enabled,
+                threads_used: r.threads_used,
+                timestamp: r.timestamp,
+                keyframes_after_dedup: r.keyframes_after_dedup,
+                analysis_start_s: 0.0,
+                analysis_duration_s: None,
+                analysis_fps: None,
+            })
+            .collect();
+
+        let errors: Vec<PyBatchFileError> = errors
+            .into_iter()
+            .map(|(video_file, error)| PyBatchFileError { video_file, error })
+            .collect();
+
+        Ok(PyBatchResult {
+            total_files: succeeded + failed,
+            succeeded,
+            failed,
+            total_frames,
+            mean_processing_fps,
+            total_time_ms,
+            results,
+            errors,
+        })
+    }
 }
 
 // 从main.rs中复制的核心函数
@@ -478,64 +1117,107 @@ struct PerformanceResult {
     simd_enabled: bool,
     threads_used: usize,
     timestamp: String,
+    keyframes_after_dedup: usize,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn extract_frames_memory_stream(
-    video_path: &PathBuf,
-    ffmpeg_path: &PathBuf,
+    video_path: impl AsRef<Path>,
+    ffmpeg_path: impl AsRef<Path>,
     max_frames: usize,
     verbose: bool,
-) -> Result<(Vec<PyVideoFrame>, usize, usize)> {
+    vfr: bool,
+    start: Option<f64>,
+    duration: Option<f64>,
+    target_fps: Option<f64>,
+    hwaccel: Option<&str>,
+) -> Result<(Vec<PyVideoFrame>, usize, usize, f64)> {
+    let video_path = video_path.as_ref();
+    let ffmpeg_path = ffmpeg_path.as_ref();
+
     if verbose {
         println!("🎬 Extracting frames using FFmpeg memory streaming...");
         println!("📁 Video: {}", video_path.display());
     }
-    
+
     // 获取视频信息
     let probe_output = Command::new(ffmpeg_path)
-        .args(["-i", video_path.to_str().unwrap(), "-hide_banner"])
+        .arg("-i").arg(video_path)
+        .arg("-hide_banner")
         .output()
         .context("Failed to probe video with FFmpeg")?;
-    
+
     let probe_info = String::from_utf8_lossy(&probe_output.stderr);
     let (width, height) = parse_video_dimensions(&probe_info)
         .ok_or_else(|| anyhow::anyhow!("Cannot parse video dimensions"))?;
-    
+    let fps = parse_video_fps(&probe_info).unwrap_or(30.0);
+
     if verbose {
         println!("📐 Video dimensions: {}x{}", width, height);
+        println!("🎞️  Detected frame rate: {:.3} fps", fps);
     }
-    
+
     // 构建优化的FFmpeg命令
     let mut cmd = Command::new(ffmpeg_path);
+    apply_hwaccel(&mut cmd, ffmpeg_path, hwaccel, verbose);
+    // -ss/-t放在-i之前走FFmpeg的输入级快速seek，避免解码整段前缀再丢弃
+    if let Some(start) = start {
+        cmd.args(["-ss", &start.to_string()]);
+    }
+    if let Some(duration) = duration {
+        cmd.args(["-t", &duration.to_string()]);
+    }
+    cmd.arg("-i").arg(video_path);
     cmd.args([
-        "-i", video_path.to_str().unwrap(),
         "-f", "rawvideo",
         "-pix_fmt", "gray",
         "-an",
         "-threads", "0",
         "-preset", "ultrafast",
     ]);
-    
+
+    // fps滤镜在降采样到目标帧率的同时，仍需要showinfo才能采集VFR的真实pts_time
+    let vf_filter = match (target_fps, vfr) {
+        (Some(fps), true) => Some(format!("fps={},showinfo", fps)),
+        (Some(fps), false) => Some(format!("fps={}", fps)),
+        (None, true) => Some("showinfo".to_string()),
+        (None, false) => None,
+    };
+    if let Some(vf_filter) = vf_filter {
+        cmd.args(["-vf", &vf_filter]);
+    }
+
     if max_frames > 0 {
         cmd.args(["-frames:v", &max_frames.to_string()]);
     }
-    
-    cmd.args(["-"]).stdout(Stdio::piped()).stderr(Stdio::null());
-    
+
+    cmd.args(["-"]).stdout(Stdio::piped());
+    cmd.stderr(if vfr { Stdio::piped() } else { Stdio::null() });
+
     let start_time = Instant::now();
     let mut child = cmd.spawn().context("Failed to spawn FFmpeg process")?;
     let stdout = child.stdout.take().unwrap();
     let mut reader = BufReader::with_capacity(1024 * 1024, stdout);
-    
+
+    // showinfo的日志走stderr，需要在单独线程里读，避免stdout管道写满时和我们一起卡死
+    let stderr_handle = vfr.then(|| {
+        let mut stderr = child.stderr.take().unwrap();
+        std::thread::spawn(move || {
+            let mut buf = String::new();
+            let _ = stderr.read_to_string(&mut buf);
+            buf
+        })
+    });
+
     let frame_size = width * height;
     let mut frames = Vec::new();
     let mut frame_count = 0;
     let mut frame_buffer = vec![0u8; frame_size];
-    
+
     if verbose {
         println!("📦 Frame size: {} bytes", frame_size);
     }
-    
+
     // 直接流式读取帧数据到内存
     loop {
         match reader.read_exact(&mut frame_buffer) {
@@ -547,11 +1229,11 @@ fn extract_frames_memory_stream(
                     frame_buffer.clone(),
                 ));
                 frame_count += 1;
-                
+
                 if verbose && frame_count % 200 == 0 {
                     print!("\r⚡ Frames processed: {}", frame_count);
                 }
-                
+
                 if max_frames > 0 && frame_count >= max_frames {
                     break;
                 }
@@ -559,28 +1241,349 @@ fn extract_frames_memory_stream(
             Err(_) => break,
         }
     }
-    
+
     let _ = child.wait();
-    
+
+    if let Some(handle) = stderr_handle {
+        let stderr_text = handle.join().unwrap_or_default();
+        let pts_times = parse_showinfo_pts_times(&stderr_text);
+        for (frame, &pts) in frames.iter_mut().zip(pts_times.iter()) {
+            frame.pts_time = Some(pts);
+        }
+    }
+
     if verbose {
-        println!("\r✅ Frame extraction complete: {} frames in {:.2}s", 
+        println!("\r✅ Frame extraction complete: {} frames in {:.2}s",
                 frame_count, start_time.elapsed().as_secs_f64());
     }
-    
-    Ok((frames, width, height))
+
+    Ok((frames, width, height, fps))
 }
 
-fn parse_video_dimensions(probe_info: &str) -> Option<(usize, usize)> {
-    for line in probe_info.lines() {
-        if line.contains("Video:") && line.contains("x") {
-            for part in line.split_whitespace() {
-                if let Some(x_pos) = part.find('x') {
-                    let width_str = &part[..x_pos];
-                    let height_part = &part[x_pos + 1..];
-                    let height_str = height_part.split(',').next().unwrap_or(height_part);
-                    
-                    if let (Ok(width), Ok(height)) = (width_str.parse::<usize>(), height_str.parse::<usize>()) {
-                        return Some((width, height));
+/// 通过扩展名（`.y4m`）或magic bytes（文件开头的`YUV4MPEG2`签名）判断输入是否是原始Y4M流，
+/// 不依赖FFmpeg探测。魔数检测会尝试读取文件开头几个字节，失败（比如路径是"-"代表的stdin，
+/// 或者不是常规文件）时不当成错误，只是回退到按扩展名判断。
+fn is_y4m_input(video_path: &Path) -> bool {
+    if video_path.extension().and_then(|e| e.to_str()).map(|e| e.eq_ignore_ascii_case("y4m")).unwrap_or(false) {
+        return true;
+    }
+    if let Ok(mut file) = fs::File::open(video_path) {
+        let mut magic = [0u8; 9];
+        if file.read_exact(&mut magic).is_ok() {
+            return &magic == b"YUV4MPEG2";
+        }
+    }
+    false
+}
+
+/// Y4M的色彩空间标签决定亮度平面之后还跟多少色度字节——读取时需要跳过这些字节才能对齐到
+/// 下一个`FRAME`标记；由于SAD/SIMD差分管线只消费灰度亮度，这里只需要算出每帧的总字节数。
+fn y4m_chroma_byte_count(colorspace: &str, width: usize, height: usize) -> usize {
+    match colorspace {
+        "mono" => 0,
+        "444" | "444alpha" => width * height * 2,
+        "422" => (width / 2) * height * 2,
+        // 未指定C参数时，Y4M规范默认是420jpeg
+        _ => ((width + 1) / 2) * ((height + 1) / 2) * 2,
+    }
+}
+
+/// 直接读取YUV4MPEG2格式：解析`YUV4MPEG2 W<width> H<height> F<num>:<den> ... C<colorspace>`
+/// 头部行拿到精确的宽高/帧率/色彩空间，然后逐个`FRAME`块读取——亮度(Y)平面正好就是
+/// SAD/SIMD差分路径要的灰度数据，直接搬进`PyVideoFrame::data`，色度平面读出来后丢弃。
+/// 不经过FFmpeg探测/管道，因此没有`parse_video_dimensions`那样scrape stderr的脆弱性，
+/// 也不会把帧率硬编码成30.0。
+fn extract_frames_y4m(
+    video_path: impl AsRef<Path>,
+    max_frames: usize,
+    verbose: bool,
+) -> Result<(Vec<PyVideoFrame>, usize, usize, f64)> {
+    let video_path = video_path.as_ref();
+    let file = fs::File::open(video_path).context("Failed to open Y4M input")?;
+    let mut reader = BufReader::with_capacity(1024 * 1024, file);
+
+    let mut header_line = Vec::new();
+    read_until_newline(&mut reader, &mut header_line).context("Failed to read Y4M header")?;
+    let header = String::from_utf8_lossy(&header_line);
+    if !header.starts_with("YUV4MPEG2") {
+        anyhow::bail!("Not a valid Y4M stream (missing YUV4MPEG2 signature)");
+    }
+
+    let mut width = 0usize;
+    let mut height = 0usize;
+    let mut fps = 30.0f64;
+    let mut colorspace = String::new();
+
+    for token in header.split_whitespace().skip(1) {
+        let (tag, value) = token.split_at(1);
+        match tag {
+            "W" => width = value.parse().unwrap_or(0),
+            "H" => height = value.parse().unwrap_or(0),
+            "F" => {
+                if let Some((num, den)) = value.split_once(':') {
+                    if let (Ok(num), Ok(den)) = (num.parse::<f64>(), den.parse::<f64>()) {
+                        if den != 0.0 {
+                            fps = num / den;
+                        }
+                    }
+                }
+            }
+            "C" => colorspace = value.to_string(),
+            _ => {}
+        }
+    }
+
+    if width == 0 || height == 0 {
+        anyhow::bail!("Cannot parse Y4M dimensions from header: {}", header.trim());
+    }
+
+    if verbose {
+        println!("📐 Y4M dimensions: {}x{}", width, height);
+        println!("🎞️  Y4M frame rate: {:.3} fps", fps);
+    }
+
+    let luma_size = width * height;
+    let chroma_size = y4m_chroma_byte_count(&colorspace, width, height);
+    let mut luma_buffer = vec![0u8; luma_size];
+    let mut chroma_buffer = vec![0u8; chroma_size];
+    let mut frame_marker = Vec::new();
+    let mut frames = Vec::new();
+    let mut frame_count = 0;
+
+    loop {
+        frame_marker.clear();
+        let bytes_read = read_until_newline(&mut reader, &mut frame_marker).context("Failed to read Y4M FRAME marker")?;
+        if bytes_read == 0 {
+            break; // EOF
+        }
+        if !frame_marker.starts_with(b"FRAME") {
+            anyhow::bail!("Expected Y4M FRAME marker, got: {}", String::from_utf8_lossy(&frame_marker));
+        }
+
+        if reader.read_exact(&mut luma_buffer).is_err() {
+            break;
+        }
+        if chroma_size > 0 && reader.read_exact(&mut chroma_buffer).is_err() {
+            break;
+        }
+
+        frames.push(PyVideoFrame::new(frame_count, width, height, luma_buffer.clone()));
+        frame_count += 1;
+
+        if verbose && frame_count % 200 == 0 {
+            print!("\r⚡ Frames processed: {}", frame_count);
+        }
+
+        if max_frames > 0 && frame_count >= max_frames {
+            break;
+        }
+    }
+
+    if verbose {
+        println!("\r✅ Y4M frame extraction complete: {} frames", frame_count);
+    }
+
+    Ok((frames, width, height, fps))
+}
+
+/// 读到换行符为止（不含换行符本身），返回读到的字节数（0代表EOF）；Y4M的header行和每个
+/// FRAME标记行长度不固定，不能像帧数据那样定长读取。
+fn read_until_newline(reader: &mut impl Read, buf: &mut Vec<u8>) -> Result<usize> {
+    let mut total = 0;
+    let mut byte = [0u8; 1];
+    loop {
+        match reader.read(&mut byte)? {
+            0 => break,
+            _ => {
+                total += 1;
+                if byte[0] == b'\n' {
+                    break;
+                }
+                buf.push(byte[0]);
+            }
+        }
+    }
+    Ok(total)
+}
+
+/// 按`decoder_backend`选择帧提取实现：`"ffmpeg_next"`走进程内解码（见`extract_frames_ffmpeg_next`），
+/// 其余取值（包括默认的`"subprocess"`）保持原有的fork FFmpeg子进程+管道读取路径，
+/// 且是唯一支持`vfr`showinfo时间戳采集、`start`/`duration`时间窗裁剪和`target_fps`降采样的后端；
+/// `ffmpeg_next`后端目前会忽略这三项，按视频原始的完整时长/帧率解码。
+///
+/// 在两者之前先判断输入是否是Y4M流（`is_y4m_input`：扩展名或`YUV4MPEG2`魔数），是的话
+/// 直接走`extract_frames_y4m`绕过FFmpeg探测——同样忽略`vfr`/`start`/`duration`/`target_fps`，
+/// 因为Y4M是已解码的原始帧序列，没有容器层面可做时间窗seek，也不需要额外抽取VFR时间戳
+/// （Y4M本身的`F`字段就是精确帧率）。
+#[allow(clippy::too_many_arguments)]
+fn extract_frames_auto(
+    video_path: impl AsRef<Path>,
+    ffmpeg_path: impl AsRef<Path>,
+    max_frames: usize,
+    verbose: bool,
+    vfr: bool,
+    start: Option<f64>,
+    duration: Option<f64>,
+    target_fps: Option<f64>,
+    hwaccel: Option<&str>,
+    decoder_backend: &str,
+) -> Result<(Vec<PyVideoFrame>, usize, usize, f64)> {
+    if is_y4m_input(video_path.as_ref()) {
+        return extract_frames_y4m(video_path, max_frames, verbose);
+    }
+    match decoder_backend {
+        "ffmpeg_next" => extract_frames_ffmpeg_next(video_path, max_frames, verbose),
+        _ => extract_frames_memory_stream(video_path, ffmpeg_path, max_frames, verbose, vfr, start, duration, target_fps, hwaccel),
+    }
+}
+
+/// 用`ffmpeg-next`在进程内直接解码：打开输入、挑选最佳视频流、建一个缩放器把解码出的帧转成
+/// 灰度（与subprocess路径的`-pix_fmt gray`保持一致），逐帧喂给调用方，省掉fork子进程和
+/// 管道拷贝的开销，也能精确seek。需要链接系统FFmpeg开发库，因此放在`ffmpeg-next-backend`
+/// feature后面，未开启该feature的构建会在选用此后端时返回明确的错误而不是编译失败。
+#[cfg(feature = "ffmpeg-next-backend")]
+fn extract_frames_ffmpeg_next(
+    video_path: impl AsRef<Path>,
+    max_frames: usize,
+    verbose: bool,
+) -> Result<(Vec<PyVideoFrame>, usize, usize, f64)> {
+    use ffmpeg_next as ffmpeg;
+    use ffmpeg::format::Pixel;
+    use ffmpeg::media::Type;
+    use ffmpeg::software::scaling::{context::Context as ScalingContext, flag::Flags};
+
+    let video_path = video_path.as_ref();
+    ffmpeg::init().context("Failed to initialize ffmpeg-next")?;
+
+    let mut ictx = ffmpeg::format::input(video_path).context("Failed to open input with ffmpeg-next")?;
+    let stream = ictx
+        .streams()
+        .best(Type::Video)
+        .ok_or_else(|| anyhow::anyhow!("No video stream found"))?;
+    let stream_index = stream.index();
+
+    let fps = {
+        let rate = stream.avg_frame_rate();
+        if rate.denominator() != 0 {
+            rate.numerator() as f64 / rate.denominator() as f64
+        } else {
+            30.0
+        }
+    };
+
+    let decoder_context = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+        .context("Failed to build decoder context")?;
+    let mut decoder = decoder_context.decoder().video().context("Failed to open video decoder")?;
+
+    let width = decoder.width() as usize;
+    let height = decoder.height() as usize;
+
+    let mut scaler = ScalingContext::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        Pixel::GRAY8,
+        decoder.width(),
+        decoder.height(),
+        Flags::BILINEAR,
+    ).context("Failed to build pixel format scaler")?;
+
+    if verbose {
+        println!("🎬 Extracting frames via in-process ffmpeg-next decode...");
+        println!("📐 Video dimensions: {}x{}", width, height);
+    }
+
+    let start_time = Instant::now();
+    let mut frames = Vec::new();
+    let mut decoded = ffmpeg::frame::Video::empty();
+    let mut gray_frame = ffmpeg::frame::Video::empty();
+
+    let mut push_decoded_frames = |decoder: &mut ffmpeg::decoder::Video, frames: &mut Vec<PyVideoFrame>| -> Result<bool> {
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            scaler.run(&decoded, &mut gray_frame).context("Failed to scale decoded frame")?;
+
+            let data = gray_frame.data(0);
+            let stride = gray_frame.stride(0);
+            let mut packed = Vec::with_capacity(width * height);
+            for row in 0..height {
+                let row_start = row * stride;
+                packed.extend_from_slice(&data[row_start..row_start + width]);
+            }
+
+            frames.push(PyVideoFrame::new(frames.len(), width, height, packed));
+
+            if verbose && frames.len() % 200 == 0 {
+                print!("\r⚡ Frames decoded: {}", frames.len());
+            }
+
+            if max_frames > 0 && frames.len() >= max_frames {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    };
+
+    'demux: for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        decoder.send_packet(&packet).context("Failed to send packet to decoder")?;
+        if push_decoded_frames(&mut decoder, &mut frames)? {
+            break 'demux;
+        }
+    }
+
+    if max_frames == 0 || frames.len() < max_frames {
+        decoder.send_eof().ok();
+        push_decoded_frames(&mut decoder, &mut frames)?;
+    }
+
+    if verbose {
+        println!("\r✅ In-process decode complete: {} frames in {:.2}s", frames.len(), start_time.elapsed().as_secs_f64());
+    }
+
+    Ok((frames, width, height, fps))
+}
+
+#[cfg(not(feature = "ffmpeg-next-backend"))]
+fn extract_frames_ffmpeg_next(
+    _video_path: impl AsRef<Path>,
+    _max_frames: usize,
+    _verbose: bool,
+) -> Result<(Vec<PyVideoFrame>, usize, usize, f64)> {
+    Err(anyhow::anyhow!(
+        "decoder_backend=\"ffmpeg_next\" requires building rust_video with the `ffmpeg-next-backend` Cargo feature (needs system FFmpeg dev libraries)"
+    ))
+}
+
+fn parse_video_dimensions(probe_info: &str) -> Option<(usize, usize)> {
+    for line in probe_info.lines() {
+        if line.contains("Video:") && line.contains("x") {
+            for part in line.split_whitespace() {
+                if let Some(x_pos) = part.find('x') {
+                    let width_str = &part[..x_pos];
+                    let height_part = &part[x_pos + 1..];
+                    let height_str = height_part.split(',').next().unwrap_or(height_part);
+
+                    if let (Ok(width), Ok(height)) = (width_str.parse::<usize>(), height_str.parse::<usize>()) {
+                        return Some((width, height));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// 从FFmpeg `-i` 的stderr探测输出中解析平均帧率（形如 "... 29.97 fps, ..." 的片段）
+fn parse_video_fps(probe_info: &str) -> Option<f64> {
+    for line in probe_info.lines() {
+        if line.contains("Video:") {
+            for part in line.split(',') {
+                let part = part.trim();
+                if let Some(num_str) = part.strip_suffix("fps") {
+                    if let Ok(fps) = num_str.trim().parse::<f64>() {
+                        return Some(fps);
                     }
                 }
             }
@@ -589,94 +1592,1150 @@ fn parse_video_dimensions(probe_info: &str) -> Option<(usize, usize)> {
     None
 }
 
+/// 从 `-vf showinfo` 的stderr日志中按出现顺序提取每一帧的 `pts_time`
+fn parse_showinfo_pts_times(stderr: &str) -> Vec<f64> {
+    let mut times = Vec::new();
+    for line in stderr.lines() {
+        if let Some(pos) = line.find("pts_time:") {
+            let rest = &line[pos + "pts_time:".len()..];
+            if let Some(token) = rest.split_whitespace().next() {
+                if let Ok(pts) = token.parse::<f64>() {
+                    times.push(pts);
+                }
+            }
+        }
+    }
+    times
+}
+
+/// 探测FFmpeg编译时支持的硬件加速器列表（`ffmpeg -hwaccels`的输出，跳过标题行）
+fn get_available_hwaccels(ffmpeg_path: impl AsRef<Path>) -> Vec<String> {
+    Command::new(ffmpeg_path.as_ref())
+        .arg("-hwaccels")
+        .arg("-hide_banner")
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .skip(1)
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 给定硬件加速器名称推断配套的`-hwaccel_output_format`，没有已知映射时直接复用加速器名本身
+fn hwaccel_output_format(name: &str) -> &str {
+    match name {
+        "cuda" => "cuda",
+        "qsv" => "qsv",
+        "vaapi" => "vaapi",
+        "videotoolbox" => "videotoolbox",
+        "d3d11va" => "d3d11",
+        other => other,
+    }
+}
+
+/// 若指定了硬件加速器且FFmpeg报告支持，则在`-i`之前插入`-hwaccel <name> -hwaccel_output_format <fmt>`；
+/// 探测不到该加速器时直接跳过，退回软件解码，而不是让FFmpeg进程直接报错退出。
+fn apply_hwaccel(cmd: &mut Command, ffmpeg_path: &Path, hwaccel: Option<&str>, verbose: bool) {
+    let Some(name) = hwaccel else { return };
+
+    if get_available_hwaccels(ffmpeg_path).iter().any(|a| a == name) {
+        cmd.arg("-hwaccel").arg(name);
+        cmd.arg("-hwaccel_output_format").arg(hwaccel_output_format(name));
+        if verbose {
+            println!("🚀 Using hardware-accelerated decode: {}", name);
+        }
+    } else if verbose {
+        println!("⚠️  Hardware accelerator '{}' not reported by `ffmpeg -hwaccels`, falling back to software decode", name);
+    }
+}
+
+/// 从ffmpeg可执行文件路径推导同目录下的ffprobe路径；如果找不到对应关系就回退到PATH里的"ffprobe"
+fn derive_ffprobe_path(ffmpeg_path: &Path) -> PathBuf {
+    let file_name = ffmpeg_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    if file_name.contains("ffmpeg") {
+        let probe_name = file_name.replacen("ffmpeg", "ffprobe", 1);
+        ffmpeg_path.with_file_name(probe_name)
+    } else {
+        PathBuf::from("ffprobe")
+    }
+}
+
+/// 用ffprobe查询码流中真正的编码器关键帧（I帧），不解码任何像素数据。
+/// 通过 `-show_entries frame=pict_type,best_effort_timestamp_time` 逐帧输出CSV，
+/// 行号即解码顺序下的帧下标，`pict_type=I` 的行就是关键帧。
+fn get_codec_keyframes_ffprobe(video_path: impl AsRef<Path>, ffprobe_path: impl AsRef<Path>) -> Result<Vec<usize>> {
+    let output = Command::new(ffprobe_path.as_ref())
+        .arg("-select_streams").arg("v")
+        .arg("-show_frames")
+        .arg("-show_entries").arg("frame=pict_type,best_effort_timestamp_time")
+        .arg("-of").arg("csv")
+        .arg(video_path.as_ref())
+        .output()
+        .context("Failed to run ffprobe for codec keyframes")?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut keyframes = Vec::new();
+    for (frame_idx, line) in stdout.lines().enumerate() {
+        let fields: Vec<&str> = line.split(',').collect();
+        // csv格式: frame,best_effort_timestamp_time,pict_type——ffprobe按自身固定的字段顺序输出，
+        // 不按-show_entries列表的顺序，所以这里不依赖列位置，只看除首列外是否有字段等于"I"
+        if fields.iter().skip(1).any(|f| f.trim() == "I") {
+            keyframes.push(frame_idx);
+        }
+    }
+    Ok(keyframes)
+}
+
+/// 用`ffprobe -skip_frame nokey`直接枚举容器里被标记为关键帧的包，拿到的`best_effort_timestamp_time`
+/// 就是这些帧的真实呈现时间戳；解码器根本不会touch非关键帧，比先解码全部帧再逐帧比对快得多。
+fn get_container_keyframe_timestamps_ffprobe(video_path: impl AsRef<Path>, ffprobe_path: impl AsRef<Path>) -> Result<Vec<f64>> {
+    let output = Command::new(ffprobe_path.as_ref())
+        .arg("-select_streams").arg("v")
+        .arg("-skip_frame").arg("nokey")
+        .arg("-show_entries").arg("frame=best_effort_timestamp_time")
+        .arg("-of").arg("csv=p=0")
+        .arg(video_path.as_ref())
+        .output()
+        .context("Failed to run ffprobe for container sync-sample keyframes")?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let timestamps = stdout
+        .lines()
+        .filter_map(|line| line.trim().parse::<f64>().ok())
+        .collect();
+    Ok(timestamps)
+}
+
+/// 递归扫描`dir`，收集扩展名（大小写不敏感）匹配`extensions`的文件路径，按路径排序后返回
+fn collect_video_files(dir: &Path, extensions: &[String]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let entries = match fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+                if extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(ext)) {
+                    files.push(path);
+                }
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// 目录批处理的核心：用rayon线程池并行跑每个文件的完整处理流程（性能统计+提取+保存），
+/// 单个文件出错只记录到错误列表里、不会让其它文件的处理中止。每完成一个文件就推进一次
+/// `progress_callback`（若提供），汇报已完成数/总数/该文件的处理FPS，方便桥接到Python侧的进度条。
+#[allow(clippy::too_many_arguments)]
+fn process_directory_batch(
+    input_dir: &Path,
+    output_dir: &Path,
+    ffmpeg_path: &Path,
+    extensions: &[String],
+    threshold: f64,
+    max_frames: usize,
+    max_save: usize,
+    use_simd: bool,
+    block_size: usize,
+    hwaccel: Option<&str>,
+    verbose: bool,
+    progress_callback: Option<PyObject>,
+) -> (Vec<PerformanceResult>, Vec<(String, String)>) {
+    let files = collect_video_files(input_dir, extensions);
+    let total = files.len();
+    let completed = AtomicUsize::new(0);
+
+    let outcomes: Vec<Result<PerformanceResult, (String, String)>> = files
+        .par_iter()
+        .map(|video_path| {
+            let video_file = video_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let file_output_dir = output_dir.join(video_path.file_stem().unwrap_or_default());
+
+            let outcome = (|| -> Result<PerformanceResult> {
+                fs::create_dir_all(&file_output_dir)
+                    .context("Failed to create per-file output directory")?;
+
+                let mut result = run_performance_test(
+                    video_path, threshold, &video_file, ffmpeg_path, max_frames, use_simd, block_size, None, None, None, hwaccel, "subprocess", verbose,
+                )?;
+
+                let (frames, _, _, fps) = extract_frames_memory_stream(video_path, ffmpeg_path, max_frames, verbose, false, None, None, None, hwaccel)?;
+                let frames: Vec<PyVideoFrame> = frames.into_iter().map(|f| PyVideoFrame {
+                    frame_number: f.frame_number,
+                    width: f.width,
+                    height: f.height,
+                    data: f.data,
+                    pts_time: f.pts_time,
+                }).collect();
+
+                let keyframe_indices = extract_keyframes_optimized(
+                    &frames, threshold, use_simd, block_size, verbose,
+                    "threshold", 0, usize::MAX, 2.5, 30, "sad",
+                )?;
+
+                save_keyframes_optimized(video_path, &keyframe_indices, &file_output_dir, ffmpeg_path, max_save, verbose, fps, None)?;
+
+                result.keyframes_after_dedup = keyframe_indices.len();
+                Ok(result)
+            })();
+
+            let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+            if let Some(callback) = &progress_callback {
+                let current_fps = outcome.as_ref().map(|r| r.processing_fps).unwrap_or(0.0);
+                Python::with_gil(|py| {
+                    let _ = callback.call1(py, (done, total, current_fps));
+                });
+            }
+
+            outcome.map_err(|e| (video_file, e.to_string()))
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(outcomes.len());
+    let mut errors = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            Ok(result) => results.push(result),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    (results, errors)
+}
+
+/// 最小的只读ISO-BMFF box遍历器，跟[`write_box`]/[`write_full_box`]成对存在——这棵树没有
+/// `Cargo.toml`能声明新依赖，与其引入完整的`mp4` crate，不如照着写box的思路反过来写一个只认
+/// 我们需要的几种box（`moov`/`trak`/`mdia`/`hdlr`/`mdhd`/`minf`/`stbl`/`stss`/`stts`）的小读取器。
+struct IsoBoxHeader {
+    box_type: [u8; 4],
+    body_start: u64,
+    body_end: u64,
+}
+
+/// 在当前文件偏移处读一个box头（支持64位largesize），返回它的body范围；读到`container_end`
+/// 或者剩余字节不够一个头时视为"没有更多box了"而不是错误，方便上层用`while let`遍历。
+fn read_box_header(reader: &mut (impl Read + Seek), container_end: u64) -> Result<Option<IsoBoxHeader>> {
+    let start = reader.stream_position()?;
+    if start + 8 > container_end {
+        return Ok(None);
+    }
+    let mut size_buf = [0u8; 4];
+    reader.read_exact(&mut size_buf)?;
+    let mut type_buf = [0u8; 4];
+    reader.read_exact(&mut type_buf)?;
+    let mut size = u32::from_be_bytes(size_buf) as u64;
+    let mut header_len = 8u64;
+    if size == 1 {
+        let mut large_size_buf = [0u8; 8];
+        reader.read_exact(&mut large_size_buf)?;
+        size = u64::from_be_bytes(large_size_buf);
+        header_len = 16;
+    } else if size == 0 {
+        size = container_end - start;
+    }
+    Ok(Some(IsoBoxHeader {
+        box_type: type_buf,
+        body_start: start + header_len,
+        body_end: start + size,
+    }))
+}
+
+/// 在`[parent_start, parent_end)`范围内顺序扫描直接子box，返回第一个类型匹配`target`的。
+fn find_child_box(reader: &mut (impl Read + Seek), parent_start: u64, parent_end: u64, target: &[u8; 4]) -> Result<Option<IsoBoxHeader>> {
+    reader.seek(SeekFrom::Start(parent_start))?;
+    while let Some(header) = read_box_header(reader, parent_end)? {
+        if &header.box_type == target {
+            return Ok(Some(header));
+        }
+        reader.seek(SeekFrom::Start(header.body_end))?;
+    }
+    Ok(None)
+}
+
+/// 直接解析MP4/MOV容器的`stbl`采样表，拿到真正的同步采样（sync sample，即编码器自己标记的
+/// 关键帧）时间戳，全程只读几个box头和两张小表，不spawn ffprobe、不碰任何像素数据。
+/// 找不到视频轨道、轨道没有`stss`（代表所有采样都是同步采样）或者文件不是ISO-BMFF时返回`Ok(None)`，
+/// 由调用方回退到基于ffprobe的[`get_container_keyframe_timestamps_ffprobe`]旁路。
+fn read_mp4_sync_sample_timestamps(video_path: impl AsRef<Path>) -> Result<Option<Vec<f64>>> {
+    let path = video_path.as_ref();
+    let is_isobmff_ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| matches!(e.to_ascii_lowercase().as_str(), "mp4" | "m4v" | "m4a" | "mov"))
+        .unwrap_or(false);
+    if !is_isobmff_ext {
+        return Ok(None);
+    }
+
+    let mut file = fs::File::open(path).context("Failed to open file for container box parsing")?;
+    let file_size = file.metadata()?.len();
+
+    let moov = match find_child_box(&mut file, 0, file_size, b"moov")? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    let mut video_mdia = None;
+    {
+        file.seek(SeekFrom::Start(moov.body_start))?;
+        while let Some(trak) = read_box_header(&mut file, moov.body_end)? {
+            if &trak.box_type == b"trak" {
+                if let Some(mdia) = find_child_box(&mut file, trak.body_start, trak.body_end, b"mdia")? {
+                    if let Some(hdlr) = find_child_box(&mut file, mdia.body_start, mdia.body_end, b"hdlr")? {
+                        file.seek(SeekFrom::Start(hdlr.body_start + 8))?;
+                        let mut handler_type = [0u8; 4];
+                        if file.read_exact(&mut handler_type).is_ok() && &handler_type == b"vide" {
+                            video_mdia = Some(mdia);
+                            break;
+                        }
+                    }
+                }
+            }
+            file.seek(SeekFrom::Start(trak.body_end))?;
+        }
+    }
+    let mdia = match video_mdia {
+        Some(mdia) => mdia,
+        None => return Ok(None),
+    };
+
+    let mdhd = match find_child_box(&mut file, mdia.body_start, mdia.body_end, b"mdhd")? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    file.seek(SeekFrom::Start(mdhd.body_start))?;
+    let mut version_buf = [0u8; 1];
+    file.read_exact(&mut version_buf)?;
+    let timescale_offset = if version_buf[0] == 1 { 4 + 8 + 8 } else { 4 + 4 + 4 };
+    file.seek(SeekFrom::Start(mdhd.body_start + timescale_offset))?;
+    let mut timescale_buf = [0u8; 4];
+    file.read_exact(&mut timescale_buf)?;
+    let timescale = u32::from_be_bytes(timescale_buf);
+    if timescale == 0 {
+        return Ok(None);
+    }
+
+    let minf = match find_child_box(&mut file, mdia.body_start, mdia.body_end, b"minf")? { Some(h) => h, None => return Ok(None) };
+    let stbl = match find_child_box(&mut file, minf.body_start, minf.body_end, b"stbl")? { Some(h) => h, None => return Ok(None) };
+
+    let stss = match find_child_box(&mut file, stbl.body_start, stbl.body_end, b"stss")? {
+        Some(header) => header,
+        // 没有stss意味着这条轨道里每个采样都是同步采样，交给上层走已有的ffprobe路径
+        None => return Ok(None),
+    };
+    file.seek(SeekFrom::Start(stss.body_start + 4))?;
+    let mut entry_count_buf = [0u8; 4];
+    file.read_exact(&mut entry_count_buf)?;
+    let mut sync_sample_numbers = Vec::with_capacity(u32::from_be_bytes(entry_count_buf) as usize);
+    for _ in 0..u32::from_be_bytes(entry_count_buf) {
+        let mut buf = [0u8; 4];
+        file.read_exact(&mut buf)?;
+        sync_sample_numbers.push(u32::from_be_bytes(buf)); // 1-based采样序号
+    }
+
+    let stts = match find_child_box(&mut file, stbl.body_start, stbl.body_end, b"stts")? { Some(h) => h, None => return Ok(None) };
+    file.seek(SeekFrom::Start(stts.body_start + 4))?;
+    let mut stts_entry_count_buf = [0u8; 4];
+    file.read_exact(&mut stts_entry_count_buf)?;
+    let mut cumulative_times = Vec::new();
+    let mut running_time: u64 = 0;
+    for _ in 0..u32::from_be_bytes(stts_entry_count_buf) {
+        let mut entry_buf = [0u8; 8];
+        file.read_exact(&mut entry_buf)?;
+        let sample_count = u32::from_be_bytes(entry_buf[0..4].try_into().unwrap());
+        let sample_delta = u32::from_be_bytes(entry_buf[4..8].try_into().unwrap()) as u64;
+        for _ in 0..sample_count {
+            cumulative_times.push(running_time);
+            running_time += sample_delta;
+        }
+    }
+
+    let timestamps: Vec<f64> = sync_sample_numbers
+        .into_iter()
+        .filter_map(|sample_number| cumulative_times.get(sample_number as usize - 1))
+        .map(|&ticks| ticks as f64 / timescale as f64)
+        .collect();
+
+    if timestamps.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(timestamps))
+}
+
+/// 容器同步采样（sync-sample）旁路：只读demuxer标记的真正编码关键帧时间戳，既不解码每一帧
+/// 也不做SIMD差分，直接按时间戳seek截取，给出一条近乎瞬时、与内容自适应检测完全分离的快速路径。
+fn process_video_container_keyframes(
+    video_path: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    ffmpeg_path: impl AsRef<Path>,
+    max_save: usize,
+    verbose: bool,
+) -> Result<PerformanceResult> {
+    let video_path = video_path.as_ref();
+    let output_dir = output_dir.as_ref();
+    let ffmpeg_path = ffmpeg_path.as_ref();
+
+    let total_start = Instant::now();
+    let (timestamps, optimization_type) = match read_mp4_sync_sample_timestamps(video_path)? {
+        Some(timestamps) => {
+            if verbose {
+                println!("📼 Container sync-sample mode: parsed stbl/stss/stts directly, no ffprobe spawned");
+            }
+            (timestamps, "Container Sync-Sample (direct stbl/stss parse)")
+        }
+        None => {
+            if verbose {
+                println!("📼 Container sync-sample mode: no usable stss box, falling back to ffprobe");
+            }
+            let ffprobe_path = derive_ffprobe_path(ffmpeg_path);
+            let timestamps = get_container_keyframe_timestamps_ffprobe(video_path, &ffprobe_path)
+                .context("Failed to read container sync-sample table")?;
+            (timestamps, "Container Sync-Sample (ffprobe fallback)")
+        }
+    };
+    let extraction_time = total_start.elapsed().as_secs_f64() * 1000.0;
+
+    let keyframe_indices: Vec<usize> = (0..timestamps.len()).collect();
+    // fps只在frame_pts缺失时才会被用到作回退换算，这里timestamps总是Some，所以数值本身无关紧要
+    let saved = save_keyframes_optimized(video_path, &keyframe_indices, output_dir, ffmpeg_path, max_save, verbose, 30.0, Some(&timestamps))?;
+
+    let total_time = total_start.elapsed().as_secs_f64() * 1000.0;
+
+    Ok(PerformanceResult {
+        test_name: "Container Sync-Sample".to_string(),
+        video_file: video_path.file_name().unwrap().to_string_lossy().to_string(),
+        total_time_ms: total_time,
+        frame_extraction_time_ms: extraction_time,
+        keyframe_analysis_time_ms: 0.0,
+        total_frames: timestamps.len(),
+        keyframes_extracted: saved,
+        keyframe_ratio: 100.0,
+        processing_fps: timestamps.len() as f64 / (total_time / 1000.0).max(f64::EPSILON),
+        threshold: 0.0,
+        optimization_type: optimization_type.to_string(),
+        simd_enabled: false,
+        threads_used: rayon::current_num_threads(),
+        timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        keyframes_after_dedup: saved,
+    })
+}
+
+/// 有界内存的流式处理：生产者线程边读FFmpeg管道边把帧塞进一个容量有限的channel，
+/// 消费者（本线程）每次只保留"上一帧"+当前帧，通过`calculate_difference_parallel_simd`
+/// 用rayon对块级SAD求和，只有关键帧的*下标*被保留下来，最终交给`save_keyframes_optimized`
+/// 重新按时间戳seek原文件截图，从而让内存占用与帧数无关、只跟channel容量和单帧大小有关。
+/// 读管道（生产者）和算差（消费者）在两个线程上重叠执行；`frame_extraction_time_ms`/
+/// `keyframe_analysis_time_ms`分别累计各自线程实际花在读/算上的时间，而不是像早期版本
+/// 那样因为两者交织就放弃拆分。
+#[allow(clippy::too_many_arguments)]
+fn process_video_streaming(
+    video_path: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    ffmpeg_path: impl AsRef<Path>,
+    threshold: f64,
+    max_frames: usize,
+    max_save: usize,
+    use_simd: bool,
+    block_size: usize,
+    mode: &str,
+    min_scene_len: usize,
+    max_scene_len: usize,
+    k: f64,
+    window_size: usize,
+    max_memory_frames: usize,
+    hwaccel: Option<&str>,
+    verbose: bool,
+) -> Result<PerformanceResult> {
+    let video_path = video_path.as_ref();
+    let output_dir = output_dir.as_ref();
+    let ffmpeg_path = ffmpeg_path.as_ref();
+
+    if verbose {
+        println!("🌊 Streaming extraction (bounded memory, max_memory_frames: {})", max_memory_frames);
+    }
+
+    let total_start = Instant::now();
+
+    let probe_output = Command::new(ffmpeg_path)
+        .arg("-i").arg(video_path)
+        .arg("-hide_banner")
+        .output()
+        .context("Failed to probe video with FFmpeg")?;
+    let probe_info = String::from_utf8_lossy(&probe_output.stderr);
+    let (width, height) = parse_video_dimensions(&probe_info)
+        .ok_or_else(|| anyhow::anyhow!("Cannot parse video dimensions"))?;
+    let fps = parse_video_fps(&probe_info).unwrap_or(30.0);
+
+    let mut cmd = Command::new(ffmpeg_path);
+    apply_hwaccel(&mut cmd, ffmpeg_path, hwaccel, verbose);
+    cmd.arg("-i").arg(video_path);
+    cmd.args([
+        "-f", "rawvideo",
+        "-pix_fmt", "gray",
+        "-an",
+        "-threads", "0",
+        "-preset", "ultrafast",
+    ]);
+    if max_frames > 0 {
+        cmd.args(["-frames:v", &max_frames.to_string()]);
+    }
+    cmd.args(["-"]).stdout(Stdio::piped()).stderr(Stdio::null());
+
+    let mut child = cmd.spawn().context("Failed to spawn FFmpeg process")?;
+    let stdout = child.stdout.take().unwrap();
+    let frame_size = width * height;
+
+    // channel容量决定"正在飞行中"的最大帧数（生产者领先消费者多少帧），配合
+    // `max_memory_frames`（至少2，因为消费者自身就要同时握着上一帧和当前帧）限定内存占用上限
+    let channel_capacity = max_memory_frames.max(2).min(64);
+    let (tx, rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(channel_capacity);
+
+    let producer_max_frames = max_frames;
+    let producer = std::thread::spawn(move || -> (usize, std::time::Duration) {
+        let mut reader = BufReader::with_capacity(1024 * 1024, stdout);
+        let mut frame_buffer = vec![0u8; frame_size];
+        let mut sent = 0usize;
+        let mut extraction_time = std::time::Duration::ZERO;
+
+        loop {
+            let read_start = Instant::now();
+            let read_ok = reader.read_exact(&mut frame_buffer).is_ok();
+            extraction_time += read_start.elapsed();
+
+            if !read_ok {
+                break;
+            }
+            if tx.send(frame_buffer.clone()).is_err() {
+                break; // 消费者已经退出（比如max_frames提前截断）
+            }
+            sent += 1;
+            if producer_max_frames > 0 && sent >= producer_max_frames {
+                break;
+            }
+        }
+
+        (sent, extraction_time)
+    });
+
+    let mut prev_frame: Option<PyVideoFrame> = None;
+    let mut frame_count = 0usize;
+    let mut analysis_time = std::time::Duration::ZERO;
+
+    let mut window: VecDeque<f64> = VecDeque::with_capacity(window_size.max(1));
+    let mut last_cut = 0usize;
+    let mut accumulator = 0.0f64;
+    let mut keyframe_indices = Vec::new();
+
+    for buf in rx.iter() {
+        let analyze_start = Instant::now();
+        let current = PyVideoFrame::new(frame_count, width, height, buf);
+
+        if let Some(prev) = &prev_frame {
+            let diff = prev.calculate_difference_parallel_simd(&current, block_size, use_simd);
+            let frame_idx = frame_count;
+            let frames_since_cut = frame_idx - last_cut;
+
+            let is_cut = if mode == "adaptive" {
+                let mean = if window.is_empty() { 0.0 } else { window.iter().sum::<f64>() / window.len() as f64 };
+                let variance = if window.is_empty() {
+                    0.0
+                } else {
+                    window.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / window.len() as f64
+                };
+                let stddev = variance.sqrt();
+                accumulator += diff;
+
+                let spike_cut = frames_since_cut >= min_scene_len.max(1) && diff > mean + k * stddev;
+                let gradual_cut = frames_since_cut >= min_scene_len.max(1) && accumulator > threshold;
+                let forced_cut = frames_since_cut >= max_scene_len;
+
+                window.push_back(diff);
+                if window.len() > window_size.max(1) {
+                    window.pop_front();
+                }
+
+                spike_cut || gradual_cut || forced_cut
+            } else {
+                diff > threshold
+            };
+
+            if is_cut {
+                keyframe_indices.push(frame_idx);
+                last_cut = frame_idx;
+                accumulator = 0.0;
+            }
+        }
+
+        analysis_time += analyze_start.elapsed();
+
+        // 只保留当前帧作为下一轮的"上一帧"，之前的像素数据随着替换被丢弃
+        prev_frame = Some(current);
+        frame_count += 1;
+
+        if verbose && frame_count % 200 == 0 {
+            print!("\r⚡ Streaming frames processed: {}", frame_count);
+        }
+    }
+
+    let (_, extraction_time_dur) = producer.join().map_err(|_| anyhow::anyhow!("Streaming producer thread panicked"))?;
+    let _ = child.wait();
+
+    if verbose {
+        println!("\r✅ Streaming analysis complete: {} frames, {} keyframes", frame_count, keyframe_indices.len());
+    }
+
+    save_keyframes_optimized(video_path, &keyframe_indices, output_dir, ffmpeg_path, max_save, verbose, fps, None)?;
+
+    let total_time = total_start.elapsed().as_secs_f64() * 1000.0;
+
+    Ok(PerformanceResult {
+        test_name: "Streaming".to_string(),
+        video_file: video_path.file_name().unwrap().to_string_lossy().to_string(),
+        total_time_ms: total_time,
+        frame_extraction_time_ms: extraction_time_dur.as_secs_f64() * 1000.0,
+        keyframe_analysis_time_ms: analysis_time.as_secs_f64() * 1000.0,
+        total_frames: frame_count,
+        keyframes_extracted: keyframe_indices.len(),
+        keyframe_ratio: keyframe_indices.len() as f64 / frame_count.max(1) as f64 * 100.0,
+        processing_fps: frame_count as f64 / (total_time / 1000.0),
+        threshold,
+        optimization_type: if use_simd {
+            format!("Streaming(bounded-memory,SIMD,block:{})", block_size)
+        } else {
+            "Streaming(bounded-memory)".to_string()
+        },
+        simd_enabled: use_simd,
+        threads_used: rayon::current_num_threads(),
+        timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        keyframes_after_dedup: keyframe_indices.len(),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
 fn extract_keyframes_optimized(
     frames: &[PyVideoFrame],
     threshold: f64,
     use_simd: bool,
     block_size: usize,
     verbose: bool,
+    mode: &str,
+    min_scene_len: usize,
+    max_scene_len: usize,
+    k: f64,
+    window_size: usize,
+    metric: &str,
 ) -> Result<Vec<usize>> {
     if frames.len() < 2 {
         return Ok(Vec::new());
     }
-    
+
     let optimization_name = if use_simd { "SIMD+Parallel" } else { "Standard Parallel" };
     if verbose {
-        println!("🚀 Keyframe analysis (threshold: {}, optimization: {})", threshold, optimization_name);
+        println!("🚀 Keyframe analysis (threshold: {}, optimization: {}, mode: {}, metric: {})", threshold, optimization_name, mode, metric);
     }
-    
+
     let start_time = Instant::now();
-    
-    // 并行计算帧差异
+
+    // 并行计算帧差异（差分计算始终是并行的，metric/mode只影响差异的计算方式和后续的扫描方式）
     let differences: Vec<f64> = frames
         .par_windows(2)
         .map(|pair| {
-            if use_simd {
+            if metric == "hist" {
+                let hist_a = compute_value_histogram(&pair[0].data);
+                let hist_b = compute_value_histogram(&pair[1].data);
+                histogram_diff(&hist_a, &hist_b)
+            } else if metric == "ssim" {
+                pair[0].calculate_ssim_diff(&pair[1])
+            } else if metric == "psnr" {
+                pair[0].calculate_psnr(&pair[1], block_size)
+            } else if use_simd {
                 pair[0].calculate_difference_parallel_simd(&pair[1], block_size, true)
             } else {
                 pair[0].calculate_difference(&pair[1]).unwrap_or(f64::MAX)
             }
         })
         .collect();
-    
-    // 基于阈值查找关键帧
-    let keyframe_indices: Vec<usize> = differences
-        .par_iter()
-        .enumerate()
-        .filter_map(|(i, &diff)| {
-            if diff > threshold {
-                Some(i + 1)
-            } else {
-                None
-            }
-        })
-        .collect();
-    
+
+    let keyframe_indices = match mode {
+        "adaptive" => detect_keyframes_adaptive(&differences, threshold, min_scene_len, max_scene_len, k, window_size),
+        "scene_adaptive" => detect_keyframes_scene_adaptive(&differences, threshold, min_scene_len, max_scene_len, k, window_size),
+        _ => {
+            // 基于固定阈值查找关键帧；PSNR和其它指标的方向相反（值越大代表两帧越接近），
+            // 所以它判的是"跌破阈值"，而不是SAD/SSIM/hist那样的"超过阈值"
+            differences
+                .par_iter()
+                .enumerate()
+                .filter_map(|(i, &diff)| {
+                    let is_cut = if metric == "psnr" { diff < threshold } else { diff > threshold };
+                    if is_cut {
+                        Some(i + 1)
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        }
+    };
+
     if verbose {
         println!("⚡ Analysis complete in {:.2}s", start_time.elapsed().as_secs_f64());
         println!("🎯 Found {} keyframes", keyframe_indices.len());
     }
-    
+
     Ok(keyframe_indices)
 }
 
+/// 内容自适应的场景切分检测（顺序扫描）
+///
+/// 维护最近 `window_size` 个帧间差异的滑动窗口；当 `diff[i] > mean(window) + k*stddev(window)`
+/// 且距离上一个切点已经过去至少 `min_scene_len` 帧时，标记为切点（用于抑制闪光/频闪造成的双重切点）。
+/// 同时累加低于阈值的差异，累加值越过 `threshold`（第二阈值）时也会触发切点，从而捕捉渐变式转场
+/// （溶解/淡入淡出）这种从不单帧突变的场景切换。`max_scene_len` 用于在场景过长时强制切出一帧。
+fn detect_keyframes_adaptive(
+    differences: &[f64],
+    threshold: f64,
+    min_scene_len: usize,
+    max_scene_len: usize,
+    k: f64,
+    window_size: usize,
+) -> Vec<usize> {
+    let mut keyframes = Vec::new();
+    let mut window: VecDeque<f64> = VecDeque::with_capacity(window_size.max(1));
+    let mut last_cut = 0usize;
+    let mut accumulator = 0.0f64;
+
+    for (i, &diff) in differences.iter().enumerate() {
+        let frame_idx = i + 1;
+        let frames_since_cut = frame_idx - last_cut;
+
+        let mean = if window.is_empty() { 0.0 } else { window.iter().sum::<f64>() / window.len() as f64 };
+        let variance = if window.is_empty() {
+            0.0
+        } else {
+            window.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / window.len() as f64
+        };
+        let stddev = variance.sqrt();
+
+        accumulator += diff;
+
+        let spike_cut = frames_since_cut >= min_scene_len.max(1) && diff > mean + k * stddev;
+        let gradual_cut = frames_since_cut >= min_scene_len.max(1) && accumulator > threshold;
+        let forced_cut = frames_since_cut >= max_scene_len;
+
+        if spike_cut || gradual_cut || forced_cut {
+            keyframes.push(frame_idx);
+            last_cut = frame_idx;
+            accumulator = 0.0;
+        }
+
+        window.push_back(diff);
+        if window.len() > window_size.max(1) {
+            window.pop_front();
+        }
+    }
+
+    keyframes
+}
+
+const HIST_BINS: usize = 16;
+
+/// 每帧的归一化亮度直方图。解码管线固定用`-pix_fmt gray`输出（见`extract_frames_memory_stream`），
+/// 只保留明度(V)通道，所以这里退化成16格的单通道直方图，而不是完整的16色相×4饱和度×4明度HSV
+/// 直方图——对纯灰度数据而言，两者在"按亮度分布检测场景切换"这件事上是等价的。
+///
+/// 范围说明：这是相对于原始需求（16色相×4饱和度×4明度的HSV直方图）的一处有意缩小——解码管线
+/// 目前只产出灰度数据，没有色度通道可用。要落地完整HSV版本需要先把`-pix_fmt gray`换成
+/// `-pix_fmt rgb24`/`yuv420p`并保留色度平面，这会改变下游SIMD差分路径的输入格式，影响面更大，
+/// 应作为单独需求重新评估，而不是顺带在这里实现。
+fn compute_value_histogram(data: &[u8]) -> [f64; HIST_BINS] {
+    let mut hist = [0f64; HIST_BINS];
+    for &v in data {
+        let bin = (v as usize * HIST_BINS) / 256;
+        hist[bin.min(HIST_BINS - 1)] += 1.0;
+    }
+    let total = data.len() as f64;
+    if total > 0.0 {
+        for h in hist.iter_mut() {
+            *h /= total;
+        }
+    }
+    hist
+}
+
+/// 两个直方图之间归一化到[0,1]的总变差距离（sum of absolute bin differences / 2）
+fn histogram_diff(a: &[f64; HIST_BINS], b: &[f64; HIST_BINS]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).sum::<f64>() / 2.0
+}
+
+/// 场景自适应检测（`mode="scene_adaptive"`）：在`detect_keyframes_adaptive`的统计条件
+/// （`diff > mean + k*stddev`）之外，额外要求差异本身超过`threshold`这个最低下限，避免窗口
+/// 刚好很平稳、标准差趋近于0时把噪声级别的抖动也判成切点；`min_scene_len`同时充当切点之间的
+/// 最小间隔，用来压住连续几帧都越过阈值造成的扎堆切点。
+fn detect_keyframes_scene_adaptive(
+    differences: &[f64],
+    threshold: f64,
+    min_scene_len: usize,
+    max_scene_len: usize,
+    k: f64,
+    window_size: usize,
+) -> Vec<usize> {
+    let mut keyframes = Vec::new();
+    let mut window: VecDeque<f64> = VecDeque::with_capacity(window_size.max(1));
+    let mut last_cut = 0usize;
+
+    for (i, &diff) in differences.iter().enumerate() {
+        let frame_idx = i + 1;
+        let frames_since_cut = frame_idx - last_cut;
+
+        let mean = if window.is_empty() { 0.0 } else { window.iter().sum::<f64>() / window.len() as f64 };
+        let variance = if window.is_empty() {
+            0.0
+        } else {
+            window.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / window.len() as f64
+        };
+        let stddev = variance.sqrt();
+
+        let above_floor = diff > threshold;
+        let above_stats = diff > mean + k * stddev;
+        let spike_cut = frames_since_cut >= min_scene_len.max(1) && above_floor && above_stats;
+        let forced_cut = frames_since_cut >= max_scene_len;
+
+        if spike_cut || forced_cut {
+            keyframes.push(frame_idx);
+            last_cut = frame_idx;
+        }
+
+        window.push_back(diff);
+        if window.len() > window_size.max(1) {
+            window.pop_front();
+        }
+    }
+
+    keyframes
+}
+
+/// 差分哈希（dHash）：先最近邻缩小到9×8灰度，再对每行相邻像素对做大小比较，每行产生8个比特，
+/// 8行拼成64位。对整体亮度/轻微编码噪声不敏感，常用于快速判断两帧是否视觉近重复。
+fn compute_dhash(data: &[u8], width: usize, height: usize) -> u64 {
+    const DHASH_W: usize = 9;
+    const DHASH_H: usize = 8;
+
+    let shrunk = downscale_nearest(data, width.max(1), height.max(1), DHASH_W, DHASH_H);
+
+    let mut hash = 0u64;
+    for row in 0..DHASH_H {
+        for col in 0..DHASH_W - 1 {
+            let bit = (shrunk[row * DHASH_W + col] < shrunk[row * DHASH_W + col + 1]) as u64;
+            hash = (hash << 1) | bit;
+        }
+    }
+    hash
+}
+
+/// 两个64位哈希之间的汉明距离（不同比特位的数量）
+fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// 按dHash贪心去重：依次扫描候选下标，只要某一帧与*已接受*的某一帧汉明距离小于`dedup_distance`
+/// 就丢弃，否则接受并记入已接受列表。保持原有顺序，只做删减，不重排。
+fn dedup_keyframes_by_hash(frames: &[PyVideoFrame], candidates: &[usize], dedup_distance: u32) -> Vec<usize> {
+    let mut accepted: Vec<usize> = Vec::with_capacity(candidates.len());
+    let mut accepted_hashes: Vec<u64> = Vec::with_capacity(candidates.len());
+
+    for &idx in candidates {
+        let frame = match frames.get(idx) {
+            Some(f) => f,
+            None => continue,
+        };
+        let hash = compute_dhash(&frame.data, frame.width, frame.height);
+
+        let is_duplicate = accepted_hashes.iter().any(|&h| hamming_distance(h, hash) < dedup_distance);
+        if !is_duplicate {
+            accepted.push(idx);
+            accepted_hashes.push(hash);
+        }
+    }
+
+    accepted
+}
+
+// 一个待切分的颜色桶，存的是像素在`pixels`里的下标而非拷贝像素本身
+struct ColorBox {
+    pixel_indices: Vec<usize>,
+}
+
+impl ColorBox {
+    fn range(&self, pixels: &[u8]) -> u32 {
+        let mut min = u8::MAX;
+        let mut max = 0u8;
+        for &i in &self.pixel_indices {
+            min = min.min(pixels[i]);
+            max = max.max(pixels[i]);
+        }
+        (max - min) as u32
+    }
+}
+
+/// median-cut调色板量化：反复对"通道range最大"的颜色桶按中位数切分，直到凑够`k`个桶，
+/// 再把每个像素映射为所在桶的平均值。当前解码帧只有单通道灰度数据，因此桶只需要按灰度值切分。
+fn median_cut_quantize(pixels: &[u8], k: usize) -> Vec<u8> {
+    let mut boxes = vec![ColorBox { pixel_indices: (0..pixels.len()).collect() }];
+
+    while boxes.len() < k.max(1) {
+        let Some((widest, _)) = boxes
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, b)| b.range(pixels))
+        else {
+            break;
+        };
+
+        if boxes[widest].pixel_indices.len() < 2 || boxes[widest].range(pixels) == 0 {
+            break;
+        }
+
+        let mut split_box = boxes.remove(widest);
+        split_box.pixel_indices.sort_by_key(|&i| pixels[i]);
+        let mid = split_box.pixel_indices.len() / 2;
+        let upper = split_box.pixel_indices.split_off(mid);
+        boxes.push(ColorBox { pixel_indices: split_box.pixel_indices });
+        boxes.push(ColorBox { pixel_indices: upper });
+    }
+
+    let mut quantized = vec![0u8; pixels.len()];
+    for b in &boxes {
+        if b.pixel_indices.is_empty() {
+            continue;
+        }
+        let sum: u32 = b.pixel_indices.iter().map(|&i| pixels[i] as u32).sum();
+        let avg = (sum / b.pixel_indices.len() as u32) as u8;
+        for &i in &b.pixel_indices {
+            quantized[i] = avg;
+        }
+    }
+    quantized
+}
+
+// 最近邻缩放，给每张关键帧生成固定尺寸的缩略图用于contact sheet排版
+fn downscale_nearest(data: &[u8], src_w: usize, src_h: usize, dst_w: usize, dst_h: usize) -> Vec<u8> {
+    let mut out = vec![0u8; dst_w * dst_h];
+    for y in 0..dst_h {
+        let src_y = (y * src_h / dst_h).min(src_h.saturating_sub(1));
+        for x in 0..dst_w {
+            let src_x = (x * src_w / dst_w).min(src_w.saturating_sub(1));
+            out[y * dst_w + x] = data[src_y * src_w + src_x];
+        }
+    }
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_montage_optimized(
+    frames: &[PyVideoFrame],
+    keyframe_indices: &[usize],
+    output_path: &Path,
+    columns: usize,
+    palette_size: usize,
+    thumb_width: usize,
+    gutter: usize,
+    verbose: bool,
+) -> Result<()> {
+    let columns = columns.max(1);
+    let thumb_width = thumb_width.max(1);
+
+    let selected: Vec<&PyVideoFrame> = keyframe_indices
+        .iter()
+        .filter_map(|&i| frames.get(i))
+        .collect();
+    if selected.is_empty() {
+        anyhow::bail!("No decoded frames matched the given keyframe indices");
+    }
+
+    let src_width = selected[0].width;
+    let src_height = selected[0].height;
+    let thumb_height = ((thumb_width * src_height) / src_width.max(1)).max(1);
+
+    let thumbnails: Vec<Vec<u8>> = selected
+        .iter()
+        .map(|frame| downscale_nearest(&frame.data, frame.width, frame.height, thumb_width, thumb_height))
+        .collect();
+
+    let rows = thumbnails.len().div_ceil(columns);
+    let canvas_width = columns * thumb_width + (columns + 1) * gutter;
+    let canvas_height = rows * thumb_height + (rows + 1) * gutter;
+    let mut canvas = vec![32u8; canvas_width * canvas_height];
+
+    for (idx, thumb) in thumbnails.iter().enumerate() {
+        let col = idx % columns;
+        let row = idx / columns;
+        let x0 = gutter + col * (thumb_width + gutter);
+        let y0 = gutter + row * (thumb_height + gutter);
+        for y in 0..thumb_height {
+            let dst_row_start = (y0 + y) * canvas_width + x0;
+            canvas[dst_row_start..dst_row_start + thumb_width]
+                .copy_from_slice(&thumb[y * thumb_width..(y + 1) * thumb_width]);
+        }
+    }
+
+    let quantized = median_cut_quantize(&canvas, palette_size);
+
+    let montage = GrayImage::from_raw(canvas_width as u32, canvas_height as u32, quantized)
+        .ok_or_else(|| anyhow::anyhow!("Failed to assemble montage canvas"))?;
+    montage.save(output_path).context("Failed to write montage image")?;
+
+    if verbose {
+        println!(
+            "🖼️  Montage saved: {} ({}x{}, {} tiles, palette size {})",
+            output_path.display(),
+            canvas_width,
+            canvas_height,
+            thumbnails.len(),
+            palette_size
+        );
+    }
+
+    Ok(())
+}
+
+/// `generate_report`每一行的多指标画像：帧号、时间戳，以及SAD/PSNR/SSIM三个指标各自的值。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FrameMetricRecord {
+    frame_index: usize,
+    timestamp_s: f64,
+    sad: f64,
+    psnr: f64,
+    ssim: f64,
+    is_keyframe: bool,
+}
+
+/// 对每一对相邻帧在一次`par_windows(2)`并行遍历里同时算三个指标：SAD复用
+/// `calculate_difference_parallel_simd`（AVX2/SSE2分块累加绝对差），PSNR复用`calculate_psnr`
+/// （同样的分块并行结构，累加的是平方差），SSIM复用`calculate_ssim_diff`（8x8块结构相似度）——
+/// 三者在同一次帧对遍历里计算，而不是为每个指标各跑一趟`extract_keyframes`；每个指标仍各自
+/// 读一遍两帧的像素数据，三者之间不共享像素累加结果。`is_keyframe`只是按
+/// SAD对`threshold`做固定阈值判断，用来给报告一个参考基线，不代表最终会用这个metric抽帧。
+fn generate_keyframe_report(
+    frames: &[PyVideoFrame],
+    threshold: f64,
+    use_simd: bool,
+    block_size: usize,
+    fps: f64,
+    frame_pts: Option<&[f64]>,
+) -> Vec<FrameMetricRecord> {
+    if frames.len() < 2 {
+        return Vec::new();
+    }
+
+    frames
+        .par_windows(2)
+        .enumerate()
+        .map(|(i, pair)| {
+            let sad = pair[0].calculate_difference_parallel_simd(&pair[1], block_size, use_simd);
+            let psnr = pair[0].calculate_psnr(&pair[1], block_size);
+            let ssim = pair[0].calculate_ssim_diff(&pair[1]);
+            let frame_index = i + 1;
+            let timestamp_s = frame_pts
+                .and_then(|pts| pts.get(frame_index))
+                .copied()
+                .unwrap_or(frame_index as f64 / fps);
+
+            FrameMetricRecord {
+                frame_index,
+                timestamp_s,
+                sad,
+                psnr,
+                ssim,
+                is_keyframe: sad > threshold,
+            }
+        })
+        .collect()
+}
+
+/// 把`generate_keyframe_report`的结果落盘：`output_path`以`.json`结尾写JSON数组，
+/// 否则写带表头的CSV，方便直接拖进表格软件或脚本里比较不同metric/threshold的分布。
+fn write_keyframe_report(records: &[FrameMetricRecord], output_path: impl AsRef<Path>) -> Result<()> {
+    let output_path = output_path.as_ref();
+    let is_json = output_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+
+    if is_json {
+        let json = serde_json::to_string_pretty(records).context("Failed to serialize keyframe report")?;
+        fs::write(output_path, json).context("Failed to write keyframe report JSON")?;
+    } else {
+        let mut csv = String::from("frame_index,timestamp_s,sad,psnr,ssim,is_keyframe\n");
+        for r in records {
+            csv.push_str(&format!(
+                "{},{:.6},{:.6},{:.6},{:.6},{}\n",
+                r.frame_index, r.timestamp_s, r.sad, r.psnr, r.ssim, r.is_keyframe
+            ));
+        }
+        fs::write(output_path, csv).context("Failed to write keyframe report CSV")?;
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn save_keyframes_optimized(
-    video_path: &PathBuf,
+    video_path: impl AsRef<Path>,
     keyframe_indices: &[usize],
-    output_dir: &PathBuf,
-    ffmpeg_path: &PathBuf,
+    output_dir: impl AsRef<Path>,
+    ffmpeg_path: impl AsRef<Path>,
     max_save: usize,
     verbose: bool,
+    fps: f64,
+    frame_pts: Option<&[f64]>,
 ) -> Result<usize> {
+    let video_path = video_path.as_ref();
+    let output_dir = output_dir.as_ref();
+    let ffmpeg_path = ffmpeg_path.as_ref();
+
     if keyframe_indices.is_empty() {
         if verbose {
             println!("⚠️  No keyframes to save");
         }
         return Ok(0);
     }
-    
+
     if verbose {
         println!("💾 Saving keyframes...");
     }
-    
+
     fs::create_dir_all(output_dir).context("Failed to create output directory")?;
-    
+
     let save_count = keyframe_indices.len().min(max_save);
     let mut saved = 0;
-    
+
     for (i, &frame_idx) in keyframe_indices.iter().take(save_count).enumerate() {
         let output_path = output_dir.join(format!("keyframe_{:03}.jpg", i + 1));
-        let timestamp = frame_idx as f64 / 30.0; // 假设30 FPS
-        
+        // 优先使用VFR采集到的真实pts_time，否则用真实帧率换算，避免硬编码30 FPS造成的漂移
+        let timestamp = frame_pts
+            .and_then(|pts| pts.get(frame_idx))
+            .copied()
+            .unwrap_or(frame_idx as f64 / fps);
+
         let output = Command::new(ffmpeg_path)
-            .args([
-                "-i", video_path.to_str().unwrap(),
-                "-ss", &timestamp.to_string(),
-                "-vframes", "1",
-                "-q:v", "2",
-                "-y",
-                output_path.to_str().unwrap(),
-            ])
+            .arg("-i").arg(video_path)
+            .args(["-ss", &timestamp.to_string(), "-vframes", "1", "-q:v", "2", "-y"])
+            .arg(&output_path)
             .output()
             .context("Failed to extract keyframe with FFmpeg")?;
         
@@ -697,32 +2756,271 @@ fn save_keyframes_optimized(
     Ok(saved)
 }
 
+/// ISO-BMFF长度前缀box写入器：先占位4字节size，写4字符box类型，跑内容闭包，最后回填大端长度。
+fn write_box(box_type: &[u8; 4], content: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+    let mut buf = vec![0u8; 4];
+    buf.extend_from_slice(box_type);
+    content(&mut buf);
+    let size = buf.len() as u32;
+    buf[0..4].copy_from_slice(&size.to_be_bytes());
+    buf
+}
+
+/// 在`write_box`之上多写version(1字节)+flags(3字节)的"full box"头，供`mvhd`/`mfhd`/`tfhd`等用。
+fn write_full_box(box_type: &[u8; 4], version: u8, flags: u32, content: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+    write_box(box_type, |buf| {
+        buf.push(version);
+        buf.extend_from_slice(&flags.to_be_bytes()[1..4]);
+        content(buf);
+    })
+}
+
+/// 顶层`ftyp`：major brand取`iso6`，兼容brand列出`iso6`/`cmfc`（CMAF常见分段品牌）。
+fn build_ftyp_box() -> Vec<u8> {
+    write_box(b"ftyp", |buf| {
+        buf.extend_from_slice(b"iso6");
+        buf.extend_from_slice(&0u32.to_be_bytes());
+        buf.extend_from_slice(b"iso6");
+        buf.extend_from_slice(b"cmfc");
+    })
+}
+
+/// 最小化的init segment：只写一个`mvhd`，不含`trak`/`mvex`等完整track描述——本工具并不解析
+/// FFmpeg `-c copy`拷出来的具体编码参数（宽高/codec/采样率等），没法诚实地生成符合规范的trak，
+/// 所以`moov`在这里只充当fMP4结构里的占位init box，标出整体`timescale`；真正可回放所需的轨道
+/// 描述仍然内嵌在各fragment `mdat`里原样拷贝的源码流字节中。
+fn build_moov_box(timescale: u32) -> Vec<u8> {
+    let mvhd = write_full_box(b"mvhd", 0, 0, |buf| {
+        buf.extend_from_slice(&0u32.to_be_bytes()); // creation_time
+        buf.extend_from_slice(&0u32.to_be_bytes()); // modification_time
+        buf.extend_from_slice(&timescale.to_be_bytes());
+        buf.extend_from_slice(&0u32.to_be_bytes()); // duration（分段产出时未知，留0）
+        buf.extend_from_slice(&0x00010000u32.to_be_bytes()); // rate 1.0
+        buf.extend_from_slice(&0x0100u16.to_be_bytes()); // volume 1.0
+        buf.extend_from_slice(&[0u8; 10]); // reserved
+        let matrix: [u32; 9] = [0x00010000, 0, 0, 0, 0x00010000, 0, 0, 0, 0x40000000];
+        for m in matrix {
+            buf.extend_from_slice(&m.to_be_bytes());
+        }
+        buf.extend_from_slice(&[0u8; 24]); // pre_defined
+        buf.extend_from_slice(&0xFFFFFFFFu32.to_be_bytes()); // next_track_id
+    });
+    write_box(b"moov", |buf| buf.extend_from_slice(&mvhd))
+}
+
+/// 一个keyframe边界内的fragment：`styp`（fragment自己的分段品牌标记，和顶层`ftyp`同结构）+
+/// `moof`（`mfhd`记录fragment序号，`traf`里`tfhd`/`tfdt`/`trun`描述这段`mdat`的时长/数据偏移）+
+/// `mdat`（真正的负载，是`segment_payload`——FFmpeg `-ss/-to -c copy`原样拷出来的码流字节）。
+/// `trun`里的`data_offset`需要在`moof`组装完、知道其总长度后才能确定，因此先占位再回填。
+fn build_fragment(sequence_number: u32, track_id: u32, sample_duration: u32, segment_payload: &[u8]) -> Vec<u8> {
+    let styp = write_box(b"styp", |buf| {
+        buf.extend_from_slice(b"msdh");
+        buf.extend_from_slice(&0u32.to_be_bytes());
+        buf.extend_from_slice(b"msdh");
+        buf.extend_from_slice(b"cmfc");
+    });
+
+    let mfhd = write_full_box(b"mfhd", 0, 0, |buf| {
+        buf.extend_from_slice(&sequence_number.to_be_bytes());
+    });
+
+    let tfhd = write_full_box(b"tfhd", 0, 0x00_0008, |buf| {
+        // flags 0x000008: default-sample-duration-present
+        buf.extend_from_slice(&track_id.to_be_bytes());
+        buf.extend_from_slice(&sample_duration.to_be_bytes());
+    });
+
+    let tfdt = write_full_box(b"tfdt", 1, 0, |buf| {
+        buf.extend_from_slice(&0u64.to_be_bytes()); // baseMediaDecodeTime，每个fragment内部从0起算
+    });
+
+    let mut trun = write_full_box(b"trun", 0, 0x00_0001, |buf| {
+        // flags 0x000001: data-offset-present；整段payload当成单个sample
+        buf.extend_from_slice(&1u32.to_be_bytes()); // sample_count
+        buf.extend_from_slice(&0i32.to_be_bytes()); // data_offset占位，组装moof后回填
+    });
+
+    let traf = write_box(b"traf", |buf| {
+        buf.extend_from_slice(&tfhd);
+        buf.extend_from_slice(&tfdt);
+        buf.extend_from_slice(&trun);
+    });
+
+    let mut moof = write_box(b"moof", |buf| {
+        buf.extend_from_slice(&mfhd);
+        buf.extend_from_slice(&traf);
+    });
+
+    // data_offset从moof起始算起，指向紧随其后的mdat负载（mdat头占8字节）
+    let data_offset = (moof.len() + 8) as i32;
+    let data_offset_pos_in_trun = 8 + 4 + 4; // size+type(8字节) + version/flags(4字节) + sample_count(4字节)
+    trun[data_offset_pos_in_trun..data_offset_pos_in_trun + 4].copy_from_slice(&data_offset.to_be_bytes());
+    let trun_offset_in_moof = moof.len() - trun.len();
+    moof[trun_offset_in_moof..moof.len()].copy_from_slice(&trun);
+
+    let mdat = write_box(b"mdat", |buf| buf.extend_from_slice(segment_payload));
+
+    let mut out = Vec::with_capacity(styp.len() + moof.len() + mdat.len());
+    out.extend_from_slice(&styp);
+    out.extend_from_slice(&moof);
+    out.extend_from_slice(&mdat);
+    out
+}
+
+/// `segments_manifest.json`里每个fragment的记录：序号、在原视频里的起始帧号、起始时间戳。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FragmentManifestEntry {
+    fragment_index: usize,
+    start_frame: usize,
+    start_pts_s: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SegmentManifest {
+    init_segment: String,
+    fragments: Vec<FragmentManifestEntry>,
+}
+
+/// `save_keyframes_optimized`的分段变体：不截JPEG静态图，而是把`keyframe_indices`当成fragment
+/// 边界，对每个`[边界i, 边界i+1)`区间用FFmpeg `-ss/-to -c copy`原样拷出码流（不重新编码），
+/// 拼成`ftyp`+`moov`+多个`styp`+`moof`+`mdat`的fragmented MP4写到`segments.mp4`，
+/// 同时写一份`segments_manifest.json`记录每个fragment的起始帧/时间戳，供下游manifest生成器使用。
+#[allow(clippy::too_many_arguments)]
+fn save_keyframes_as_segments(
+    video_path: impl AsRef<Path>,
+    keyframe_indices: &[usize],
+    output_dir: impl AsRef<Path>,
+    ffmpeg_path: impl AsRef<Path>,
+    total_frames: usize,
+    verbose: bool,
+    fps: f64,
+    frame_pts: Option<&[f64]>,
+) -> Result<usize> {
+    let video_path = video_path.as_ref();
+    let output_dir = output_dir.as_ref();
+    let ffmpeg_path = ffmpeg_path.as_ref();
+
+    fs::create_dir_all(output_dir).context("Failed to create output directory")?;
+
+    let pts_at = |frame_idx: usize| -> f64 {
+        frame_pts.and_then(|pts| pts.get(frame_idx)).copied().unwrap_or(frame_idx as f64 / fps)
+    };
+
+    let mut boundaries: Vec<usize> = Vec::with_capacity(keyframe_indices.len() + 2);
+    boundaries.push(0);
+    boundaries.extend(keyframe_indices.iter().copied());
+    boundaries.push(total_frames);
+    boundaries.dedup();
+
+    const TIMESCALE: u32 = 1000;
+
+    let mut segments_file = build_ftyp_box();
+    segments_file.extend_from_slice(&build_moov_box(TIMESCALE));
+
+    let mut manifest_entries = Vec::new();
+    let mut fragment_count = 0usize;
+
+    if verbose {
+        println!("📦 Emitting fragmented MP4 segments...");
+    }
+
+    for window in boundaries.windows(2) {
+        let (start_frame, end_frame) = (window[0], window[1]);
+        if start_frame >= end_frame {
+            continue;
+        }
+        let start_s = pts_at(start_frame);
+        let end_s = pts_at((end_frame - 1).min(total_frames.saturating_sub(1)).max(start_frame));
+
+        let segment_out = output_dir.join(format!("_segment_{:04}.mp4", fragment_count));
+        let output = Command::new(ffmpeg_path)
+            .arg("-i").arg(video_path)
+            .args(["-ss", &start_s.to_string(), "-to", &end_s.to_string(), "-c", "copy", "-y"])
+            .arg(&segment_out)
+            .output()
+            .context("Failed to cut segment with FFmpeg")?;
+
+        if !output.status.success() {
+            if verbose {
+                eprintln!("⚠️  Failed to cut segment at frame {}", start_frame);
+            }
+            continue;
+        }
+
+        let payload = fs::read(&segment_out).context("Failed to read cut segment")?;
+        let _ = fs::remove_file(&segment_out);
+
+        let sample_duration = ((end_s - start_s) * TIMESCALE as f64).round().max(0.0) as u32;
+        let fragment = build_fragment(fragment_count as u32 + 1, 1, sample_duration, &payload);
+        segments_file.extend_from_slice(&fragment);
+
+        manifest_entries.push(FragmentManifestEntry {
+            fragment_index: fragment_count,
+            start_frame,
+            start_pts_s: start_s,
+        });
+        fragment_count += 1;
+
+        if verbose && (fragment_count % 10 == 0) {
+            print!("\r📦 Fragments written: {}", fragment_count);
+        }
+    }
+
+    let segments_path = output_dir.join("segments.mp4");
+    fs::write(&segments_path, &segments_file).context("Failed to write segmented MP4")?;
+
+    let manifest = SegmentManifest {
+        init_segment: segments_path.file_name().unwrap().to_string_lossy().to_string(),
+        fragments: manifest_entries,
+    };
+    let manifest_path = output_dir.join("segments_manifest.json");
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?).context("Failed to write segment manifest")?;
+
+    if verbose {
+        println!("\r✅ Segment emission complete: {} fragments", fragment_count);
+    }
+
+    Ok(fragment_count)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run_performance_test(
-    video_path: &PathBuf,
+    video_path: impl AsRef<Path>,
     threshold: f64,
     test_name: &str,
-    ffmpeg_path: &PathBuf,
+    ffmpeg_path: impl AsRef<Path>,
     max_frames: usize,
     use_simd: bool,
     block_size: usize,
+    start: Option<f64>,
+    duration: Option<f64>,
+    target_fps: Option<f64>,
+    hwaccel: Option<&str>,
+    decoder_backend: &str,
     verbose: bool,
 ) -> Result<PerformanceResult> {
+    let video_path = video_path.as_ref();
+    let ffmpeg_path = ffmpeg_path.as_ref();
+
     if verbose {
         println!("\n{}", "=".repeat(60));
         println!("⚡ Running test: {}", test_name);
         println!("{}", "=".repeat(60));
     }
-    
+
     let total_start = Instant::now();
-    
+
     // 帧提取
     let extraction_start = Instant::now();
-    let (frames, _width, _height) = extract_frames_memory_stream(video_path, ffmpeg_path, max_frames, verbose)?;
+    let (frames, _width, _height, _fps) = extract_frames_auto(video_path, ffmpeg_path, max_frames, verbose, false, start, duration, target_fps, hwaccel, decoder_backend)?;
     let extraction_time = extraction_start.elapsed().as_secs_f64() * 1000.0;
     
     // 关键帧分析
     let analysis_start = Instant::now();
-    let keyframe_indices = extract_keyframes_optimized(&frames, threshold, use_simd, block_size, verbose)?;
+    let keyframe_indices = extract_keyframes_optimized(
+        &frames, threshold, use_simd, block_size, verbose,
+        "threshold", 0, usize::MAX, 2.5, 30, "sad",
+    )?;
     let analysis_time = analysis_start.elapsed().as_secs_f64() * 1000.0;
     
     let total_time = total_start.elapsed().as_secs_f64() * 1000.0;
@@ -748,6 +3046,7 @@ fn run_performance_test(
         simd_enabled: use_simd,
         threads_used: rayon::current_num_threads(),
         timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        keyframes_after_dedup: keyframe_indices.len(),
     };
     
     if verbose {
@@ -771,11 +3070,14 @@ fn run_performance_test(
 fn rust_video(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyVideoFrame>()?;
     m.add_class::<PyPerformanceResult>()?;
+    m.add_class::<PyBatchFileError>()?;
+    m.add_class::<PyBatchResult>()?;
     m.add_class::<VideoKeyframeExtractor>()?;
     
     // 便捷函数
     #[pyfn(m)]
-    #[pyo3(signature = (video_path, output_dir, threshold=None, max_frames=None, max_save=None, ffmpeg_path=None, use_simd=None, threads=None, verbose=None))]
+    #[pyo3(signature = (video_path, output_dir, threshold=None, max_frames=None, max_save=None, ffmpeg_path=None, use_simd=None, threads=None, verbose=None, hwaccel=None, decoder_backend=None, start=None, duration=None, fps=None, emit_segments=None))]
+    #[allow(clippy::too_many_arguments)]
     fn extract_keyframes_from_video(
         video_path: &str,
         output_dir: &str,
@@ -785,14 +3087,24 @@ fn rust_video(m: &Bound<'_, PyModule>) -> PyResult<()> {
         ffmpeg_path: Option<String>,
         use_simd: Option<bool>,
         threads: Option<usize>,
-        verbose: Option<bool>
+        verbose: Option<bool>,
+        hwaccel: Option<String>,
+        decoder_backend: Option<String>,
+        start: Option<f64>,
+        duration: Option<f64>,
+        fps: Option<f64>,
+        emit_segments: Option<bool>,
     ) -> PyResult<PyPerformanceResult> {
         let extractor = VideoKeyframeExtractor::new(
             ffmpeg_path.unwrap_or_else(|| "ffmpeg".to_string()),
             threads.unwrap_or(0),
-            verbose.unwrap_or(false)
+            verbose.unwrap_or(false),
+            false,
+            0,
+            hwaccel,
+            decoder_backend,
         )?;
-        
+
         extractor.process_video(
             video_path,
             output_dir,
@@ -800,10 +3112,57 @@ fn rust_video(m: &Bound<'_, PyModule>) -> PyResult<()> {
             max_frames,
             max_save,
             use_simd,
-            None
+            None,
+            None,
+            None,
+            None,
+            start,
+            duration,
+            fps,
+            emit_segments,
         )
     }
-    
+
+    #[pyfn(m)]
+    #[pyo3(signature = (input_dir, output_dir, extensions=None, threshold=None, max_frames=None, max_save=None, ffmpeg_path=None, use_simd=None, threads=None, verbose=None, hwaccel=None, progress_callback=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn process_directory(
+        input_dir: &str,
+        output_dir: &str,
+        extensions: Option<Vec<String>>,
+        threshold: Option<f64>,
+        max_frames: Option<usize>,
+        max_save: Option<usize>,
+        ffmpeg_path: Option<String>,
+        use_simd: Option<bool>,
+        threads: Option<usize>,
+        verbose: Option<bool>,
+        hwaccel: Option<String>,
+        progress_callback: Option<PyObject>,
+    ) -> PyResult<PyBatchResult> {
+        let extractor = VideoKeyframeExtractor::new(
+            ffmpeg_path.unwrap_or_else(|| "ffmpeg".to_string()),
+            threads.unwrap_or(0),
+            verbose.unwrap_or(false),
+            false,
+            0,
+            hwaccel,
+            None,
+        )?;
+
+        extractor.process_directory(
+            input_dir,
+            output_dir,
+            extensions,
+            threshold,
+            max_frames,
+            max_save,
+            use_simd,
+            None,
+            progress_callback,
+        )
+    }
+
     #[pyfn(m)]
     fn get_system_info() -> PyResult<HashMap<String, PyObject>> {
         Python::with_gil(|py| {
@@ -816,13 +3175,21 @@ fn rust_video(m: &Bound<'_, PyModule>) -> PyResult<()> {
                 info.insert("sse2_supported".to_string(), std::arch::is_x86_feature_detected!("sse2").to_object(py));
             }
             
-            #[cfg(not(target_arch = "x86_64"))]
+            #[cfg(target_arch = "aarch64")]
+            {
+                info.insert("neon_supported".to_string(), std::arch::is_aarch64_feature_detected!("neon").to_object(py));
+            }
+
+            #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
             {
                 info.insert("simd_supported".to_string(), false.to_object(py));
             }
             
             info.insert("version".to_string(), "0.1.0".to_object(py));
-            
+
+            let hwaccels = get_available_hwaccels("ffmpeg");
+            info.insert("hwaccels_available".to_string(), hwaccels.to_object(py));
+
             Ok(info)
         })
     }